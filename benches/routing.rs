@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use hyper::Method;
+use hyper_accelerator::{
+    application_context_trait::ApplicationContextTrait,
+    request_context_trait::RequestContextTrait,
+    request_handler::{ErrorResponse, Request, Response},
+    routing::{PathParams, Router, RouterBuilder},
+};
+
+const ROUTE_COUNTS: [usize; 3] = [10, 100, 400];
+
+struct ApplicationContext;
+
+impl ApplicationContextTrait for ApplicationContext {}
+
+struct RequestContext;
+
+impl RequestContextTrait<ApplicationContext> for RequestContext {
+    fn create(_app_context: Arc<ApplicationContext>) -> Self {
+        Self
+    }
+}
+
+async fn handler(
+    _req: Request,
+    _app_context: Arc<ApplicationContext>,
+    _request_context: RequestContext,
+) -> Result<Response, ErrorResponse> {
+    Ok(Response::default())
+}
+
+async fn handler_with_params(
+    _req: Request,
+    _app_context: Arc<ApplicationContext>,
+    _request_context: RequestContext,
+    _params: PathParams,
+) -> Result<Response, ErrorResponse> {
+    Ok(Response::default())
+}
+
+/// Every resource has a list route and a parameterised route.
+fn tree_router(resource_count: usize) -> Router<ApplicationContext, RequestContext> {
+    let mut builder = RouterBuilder::new();
+    for i in 0..resource_count {
+        builder = builder
+            .path(&[Method::GET], format!("/api/resource{i}"), handler)
+            .unwrap()
+            .route(
+                &[Method::GET],
+                &format!("/api/resource{i}/{{id:u64}}/items/{{item}}"),
+                handler_with_params,
+            )
+            .unwrap();
+    }
+    builder.build(ApplicationContext)
+}
+
+/// The same routes as [`tree_router`] as regexes, which are scanned linearly.
+fn regex_router(resource_count: usize) -> Router<ApplicationContext, RequestContext> {
+    let mut builder = RouterBuilder::new();
+    for i in 0..resource_count {
+        builder = builder
+            .path(&[Method::GET], format!(r"/api/resource{i}/?"), handler)
+            .unwrap()
+            .path(
+                &[Method::GET],
+                format!(r"/api/resource{i}/(\d+)/items/([^/]+)"),
+                handler,
+            )
+            .unwrap();
+    }
+    builder.build(ApplicationContext)
+}
+
+fn route_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("route_lookup");
+
+    for resource_count in ROUTE_COUNTS {
+        let last = resource_count - 1;
+        let paths = [
+            ("first", "/api/resource0/42/items/ball".to_string()),
+            ("last", format!("/api/resource{last}/42/items/ball")),
+            ("static", format!("/api/resource{last}")),
+            ("missing", "/api/unknown/42".to_string()),
+        ];
+
+        for (router_name, router) in [
+            ("tree", tree_router(resource_count)),
+            ("regex", regex_router(resource_count)),
+        ] {
+            for (path_name, path) in paths.iter() {
+                group.bench_with_input(
+                    BenchmarkId::new(format!("{router_name}/{path_name}"), resource_count * 2),
+                    path,
+                    |b, path| b.iter(|| router.find_route(&Method::GET, path)),
+                );
+            }
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, route_lookup);
+criterion_main!(benches);
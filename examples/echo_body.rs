@@ -5,13 +5,12 @@ use std::sync::Arc;
 use clap::Parser;
 use hyper_accelerator::{
     application_context_trait::ApplicationContextTrait,
-    body_ext::BodyExt,
     content_type::ContentType,
     error::Error,
-    prelude::ResultInspector,
+    extract::{read_body, DEFAULT_BODY_LIMIT},
     request_context_trait::RequestContextTrait,
     request_handler::{ErrorResponse, Request, Response},
-    response::{create_bytes_response, create_empty_response},
+    response::create_bytes_response,
     server::run_http1_tcp_server,
 };
 
@@ -42,11 +41,6 @@ impl RequestContextTrait<ApplicationContext> for RequestContext {
     }
 }
 
-#[derive(serde::Serialize)]
-struct SerializableResponse {
-    message: String,
-}
-
 async fn echo_body(
     req: Request,
     _app_context: Arc<ApplicationContext>,
@@ -60,13 +54,7 @@ async fn echo_body(
         .cloned()
         .unwrap_or_else(|| ContentType::ApplicationOctetstream.into());
 
-    let (payload, _trailers) = body
-        .collect()
-        .await
-        .inspect_err(|e| log::warn!("Error while reading payload, error = {e:?}"))
-        .map_err(|_| create_empty_response(hyper::StatusCode::INTERNAL_SERVER_ERROR))?
-        .aggregate();
-    let payload = payload.ok_or_else(|| create_empty_response(hyper::StatusCode::BAD_REQUEST))?;
+    let payload = read_body(body, DEFAULT_BODY_LIMIT).await?;
 
     Ok(create_bytes_response(
         hyper::StatusCode::OK,
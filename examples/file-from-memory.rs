@@ -9,7 +9,6 @@ use hyper_accelerator::{
     body_utils::create_static_str_body,
     content_type::ContentType,
     error::Error,
-    prelude::ResultInspector,
     request_context_trait::RequestContextTrait,
     request_handler::{ErrorResponse, Request, Response},
     response::{create_empty_response, create_file_response},
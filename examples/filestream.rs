@@ -10,7 +10,6 @@ use hyper_accelerator::{
     content_type::ContentType,
     error::Error,
     filestream::FileStream,
-    prelude::ResultInspector,
     request_context_trait::RequestContextTrait,
     request_handler::{ErrorResponse, Request, Response},
     response::{create_empty_response, create_file_response},
@@ -2,10 +2,8 @@ use std::sync::Arc;
 
 use clap::Parser;
 use hyper_accelerator::{
-    application_context_trait::ApplicationContextTrait,
-    error::Error,
-    request_context_trait::RequestContextTrait,
-    request_handler::{ErrorResponse, Request, Response},
+    application_context_trait::ApplicationContextTrait, error::Error, into_response::handler,
+    request_context_trait::RequestContextTrait, request_handler::Request,
     server::run_http1_tcp_server,
 };
 
@@ -40,8 +38,8 @@ async fn hello(
     _req: Request,
     _app_context: Arc<ApplicationContext>,
     _request_context: RequestContext,
-) -> Result<Response, ErrorResponse> {
-    Ok(Response::new("Hello World!".into()))
+) -> &'static str {
+    "Hello World!"
 }
 
 #[tokio::main]
@@ -54,7 +52,8 @@ async fn main() -> Result<(), Error> {
 
     log::info!("Starting application!");
 
-    let server_task = run_http1_tcp_server(cli.listener_address, hello, ApplicationContext).await?;
+    let server_task =
+        run_http1_tcp_server(cli.listener_address, handler(hello), ApplicationContext).await?;
     server_task.await??;
 
     Ok(())
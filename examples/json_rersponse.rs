@@ -7,7 +7,6 @@ use hyper::StatusCode;
 use hyper_accelerator::{
     application_context_trait::ApplicationContextTrait,
     error::Error,
-    prelude::ResultInspector,
     request_context_trait::RequestContextTrait,
     request_handler::{ErrorResponse, Request, Response},
     response::{create_empty_response, create_json_response},
@@ -1,6 +1,6 @@
 #![allow(unstable_name_collisions)]
 
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use clap::Parser;
 use hyper::StatusCode;
@@ -9,11 +9,11 @@ use hyper_accelerator::{
     create_request_handler_call_chain,
     decorators::{debug_log_cookies, debug_log_headers, debug_log_request_line},
     error::Error,
-    prelude::ResultInspector,
+    extract::parse_query,
     request_context_trait::RequestContextTrait,
     request_handler::{ErrorResponse, Request, Response},
     response::{create_empty_response, create_json_response},
-    routing::{router_fn, RouterBuilder},
+    routing::{router_fn, PathParams, RouterBuilder},
     server::run_http1_tcp_server,
 };
 
@@ -52,7 +52,7 @@ async fn index(
     let response_body = "/ -> this help message\n\
         /hello -> sends hello back\n\
         /echo?<query_params> -> echoes every query param back\n\
-        /resources/<resource-id> -> queries the resource with the given id";
+        /resources/<resource-id> -> queries the resource with the given numeric id";
     Ok(Response::new(response_body.into()))
 }
 
@@ -69,16 +69,17 @@ async fn echo(
     _app_context: Arc<ApplicationContext>,
     _request_context: RequestContext,
 ) -> Result<Response, ErrorResponse> {
-    let mut response_body = String::new();
+    let params: BTreeMap<String, Vec<String>> = parse_query(&req)?;
 
-    for param in querystring::querify(req.uri().query().unwrap_or("")) {
-        let name = param.0;
-        let value = param.1;
+    let mut response_body = String::new();
 
-        response_body += name;
-        response_body += ": ";
-        response_body += value;
-        response_body += "\n";
+    for (name, values) in params {
+        for value in values {
+            response_body += &name;
+            response_body += ": ";
+            response_body += &value;
+            response_body += "\n";
+        }
     }
 
     Ok(Response::new(response_body.into()))
@@ -86,15 +87,17 @@ async fn echo(
 
 #[derive(serde::Serialize)]
 struct Resource {
-    id: String,
+    id: u64,
 }
 
 async fn resource_by_id(
     _req: Request,
     _app_context: Arc<ApplicationContext>,
     _request_context: RequestContext,
-    id: String,
+    params: PathParams,
 ) -> Result<Response, ErrorResponse> {
+    let id = params.parse("id")?;
+
     Ok(create_json_response(StatusCode::OK, &Resource { id })
         .inspect_err(|e| log::error!("Could not create JSON response, error = {:?}", e))
         .map_err(|_| create_empty_response(StatusCode::INTERNAL_SERVER_ERROR))?)
@@ -114,18 +117,7 @@ async fn main() -> Result<(), Error> {
         .path(&[hyper::Method::GET], r"/", index)?
         .path(&[hyper::Method::GET], r"/hello", hello)?
         .path(&[hyper::Method::GET], r"/echo", echo)?
-        .path_with_params(
-            &[hyper::Method::GET],
-            r"/resources/(\w+)",
-            |req, app_context, request_context, path_captures| {
-                resource_by_id(
-                    req,
-                    app_context,
-                    request_context,
-                    path_captures[1].to_string(),
-                )
-            },
-        )?
+        .route(&[hyper::Method::GET], "/resources/{id:u64}", resource_by_id)?
         .build(ApplicationContext);
 
     let server_task = run_http1_tcp_server(
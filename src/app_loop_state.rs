@@ -2,11 +2,34 @@ use std::sync::Arc;
 
 use tokio::sync::watch::{self, Receiver, Sender};
 
+#[cfg(unix)]
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    task::JoinHandle,
+};
+
+/// Exit code used when a second quit signal forces the process to exit.
+#[cfg(unix)]
+pub const FORCED_EXIT_CODE: i32 = 130;
+
 #[derive(Clone)]
-pub struct AppLoopState(Arc<Sender<bool>>);
+pub struct AppLoopState {
+    run_loop: Arc<Sender<bool>>,
+    reload_count: Arc<Sender<u64>>,
+}
 
 #[derive(Clone)]
-pub struct AppLoopStateWatcher(Receiver<bool>);
+pub struct AppLoopStateWatcher {
+    run_loop: Receiver<bool>,
+    reload_count: Receiver<u64>,
+}
+
+/// What [`AppLoopStateWatcher::wait_for_event`] woke up for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AppLoopEvent {
+    Quit,
+    Reload,
+}
 
 impl Default for AppLoopState {
     fn default() -> Self {
@@ -16,31 +39,119 @@ impl Default for AppLoopState {
 
 impl AppLoopState {
     pub fn new() -> Self {
-        let (sender, _receiver) = watch::channel(true);
+        let (run_loop, _receiver) = watch::channel(true);
+        let (reload_count, _receiver) = watch::channel(0);
 
-        Self(Arc::new(sender))
+        Self {
+            run_loop: Arc::new(run_loop),
+            reload_count: Arc::new(reload_count),
+        }
     }
 
     pub fn watcher(&self) -> AppLoopStateWatcher {
-        AppLoopStateWatcher(self.0.subscribe())
+        AppLoopStateWatcher {
+            run_loop: self.run_loop.subscribe(),
+            reload_count: self.reload_count.subscribe(),
+        }
     }
 
     pub fn stop_loop(&self) {
-        let _ = self.0.send(false);
+        let _ = self.run_loop.send(false);
     }
 
     pub fn should_run(&self) -> bool {
-        *self.0.borrow()
+        *self.run_loop.borrow()
+    }
+
+    /// Wakes up every [`AppLoopStateWatcher::wait_for_reload`] call.
+    pub fn reload(&self) {
+        self.reload_count
+            .send_modify(|reload_count| *reload_count += 1);
+    }
+
+    /// Installs handlers for SIGINT and SIGTERM that call [`AppLoopState::stop_loop`], and for
+    /// SIGHUP that calls [`AppLoopState::reload`].
+    ///
+    /// A quit signal received after the loop was already stopped exits the process with
+    /// [`FORCED_EXIT_CODE`]. The signals are handled until the returned task is aborted. tokio
+    /// keeps its process-wide handlers installed after that, so e.g. SIGINT does not terminate
+    /// the process anymore, see [`tokio::signal::unix::signal`].
+    #[cfg(unix)]
+    pub fn handle_signals(&self) -> Result<JoinHandle<()>, std::io::Error> {
+        self.handle_signals_with_forced_exit(|| std::process::exit(FORCED_EXIT_CODE))
+    }
+
+    /// Same as [`AppLoopState::handle_signals`], but calls `forced_exit` instead of exiting when
+    /// a quit signal arrives after the loop was already stopped.
+    #[cfg(unix)]
+    pub fn handle_signals_with_forced_exit(
+        &self,
+        forced_exit: impl FnOnce() + Send + 'static,
+    ) -> Result<JoinHandle<()>, std::io::Error> {
+        let mut interrupt = signal(SignalKind::interrupt())?;
+        let mut terminate = signal(SignalKind::terminate())?;
+        let mut hangup = signal(SignalKind::hangup())?;
+
+        let app_loop_state = self.clone();
+        Ok(tokio::spawn(async move {
+            let mut forced_exit = Some(forced_exit);
+            loop {
+                tokio::select! {
+                    _ = interrupt.recv() => {}
+                    _ = terminate.recv() => {}
+                    _ = hangup.recv() => {
+                        log::info!("Reload signal received");
+                        app_loop_state.reload();
+                        continue;
+                    }
+                }
+
+                if app_loop_state.should_run() {
+                    log::info!("Quit signal received, stopping");
+                    app_loop_state.stop_loop();
+                } else if let Some(forced_exit) = forced_exit.take() {
+                    log::warn!("Quit signal received while stopping, forcing exit");
+                    forced_exit();
+                }
+            }
+        }))
     }
 }
 
 impl AppLoopStateWatcher {
     pub fn should_run(&self) -> bool {
-        *self.0.borrow()
+        *self.run_loop.borrow()
+    }
+
+    /// Resolves on the next [`AppLoopState::reload`] since the previous call, or since the
+    /// watcher was created.
+    pub async fn wait_for_reload(&mut self) {
+        if self.reload_count.changed().await.is_err() {
+            std::future::pending().await
+        }
+    }
+
+    /// Resolves on quit or on the next reload, whichever comes first. Quit wins if both are
+    /// pending.
+    pub async fn wait_for_event(&mut self) -> AppLoopEvent {
+        if !self.should_run() {
+            return AppLoopEvent::Quit;
+        }
+
+        let run_loop = self.clone();
+        tokio::select! {
+            biased;
+            _ = run_loop.wait_for_quit() => AppLoopEvent::Quit,
+            _ = self.wait_for_reload() => AppLoopEvent::Reload,
+        }
     }
 
     pub async fn wait_for_quit(&self) {
-        let mut run_loop = self.0.clone();
+        let mut run_loop = self.run_loop.clone();
+        if !*run_loop.borrow() {
+            return;
+        }
+
         while let Ok(()) = run_loop.changed().await {
             if !*run_loop.borrow() {
                 break;
@@ -55,7 +166,7 @@ mod tests {
 
     use tokio::time::{sleep_until, Instant};
 
-    use super::AppLoopState;
+    use super::{AppLoopEvent, AppLoopState};
 
     #[tokio::test(flavor = "multi_thread")]
     async fn quit() {
@@ -68,15 +179,89 @@ mod tests {
 
         let timeout = Duration::from_secs(1);
         let timestamp = Instant::now() + timeout;
-        loop {
-            tokio::select! {
-                _ = sleep_until(timestamp) => {
-                    break;
-                }
-                _ = state_watcher.wait_for_quit() => {
-                    break;
-                }
-            }
+        tokio::select! {
+            _ = sleep_until(timestamp) => {}
+            _ = state_watcher.wait_for_quit() => {}
         }
     }
+
+    #[tokio::test]
+    async fn quit_before_waiting() {
+        let state = AppLoopState::new();
+        let state_watcher = state.watcher();
+
+        state.stop_loop();
+
+        tokio::time::timeout(Duration::from_secs(1), state_watcher.wait_for_quit())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn reload_then_quit() {
+        let state = AppLoopState::new();
+        let mut state_watcher = state.watcher();
+
+        state.reload();
+        assert_eq!(state_watcher.wait_for_event().await, AppLoopEvent::Reload);
+
+        state.stop_loop();
+        assert_eq!(state_watcher.wait_for_event().await, AppLoopEvent::Quit);
+    }
+
+    #[cfg(unix)]
+    fn send_signal(signal: &str) {
+        let status = std::process::Command::new("kill")
+            .arg(format!("-{signal}"))
+            .arg(std::process::id().to_string())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    #[serial_test::serial(signals)]
+    async fn hangup_signal_reloads() {
+        let state = AppLoopState::new();
+        let mut state_watcher = state.watcher();
+        let signal_task = state.handle_signals().unwrap();
+
+        send_signal("HUP");
+
+        let event = tokio::time::timeout(Duration::from_secs(1), state_watcher.wait_for_event())
+            .await
+            .unwrap();
+        assert_eq!(event, AppLoopEvent::Reload);
+        assert!(state.should_run());
+
+        signal_task.abort();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    #[serial_test::serial(signals)]
+    async fn second_quit_signal_forces_exit() {
+        let state = AppLoopState::new();
+        let state_watcher = state.watcher();
+        let (forced_exit_sender, forced_exit_receiver) = tokio::sync::oneshot::channel();
+        let signal_task = state
+            .handle_signals_with_forced_exit(move || {
+                let _ = forced_exit_sender.send(());
+            })
+            .unwrap();
+
+        send_signal("TERM");
+        tokio::time::timeout(Duration::from_secs(1), state_watcher.wait_for_quit())
+            .await
+            .unwrap();
+
+        send_signal("INT");
+        tokio::time::timeout(Duration::from_secs(1), forced_exit_receiver)
+            .await
+            .unwrap()
+            .unwrap();
+
+        signal_task.abort();
+    }
 }
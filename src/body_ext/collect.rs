@@ -47,7 +47,7 @@ where
                     if let Ok(trailers) = frame.into_trailers() {
                         aggregated_trailers
                             .get_or_insert_with(hyper::HeaderMap::new)
-                            .extend(trailers.into_iter());
+                            .extend(trailers);
                     }
                 }
             }
@@ -0,0 +1,57 @@
+use std::{ops::Deref, sync::Arc};
+
+use crate::{
+    application_context_trait::ApplicationContextTrait,
+    request_context_trait::RequestContextTrait,
+    request_handler::{
+        ErrorResponse, Request, RequestHandlerFn, RequestHandlerReturnTrait, Response,
+    },
+    response::create_empty_response,
+    server::catch_unwind::{panic_message, CatchUnwind},
+};
+
+pub trait CatchPanicApplicationContext {
+    /// The response sent when the wrapped request handler panics.
+    fn panic_response(&self) -> Response {
+        create_empty_response(hyper::StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+/// Turns a panic of the wrapped request handler into
+/// [`CatchPanicApplicationContext::panic_response`] and logs it with the request line.
+pub async fn catch_panic<
+    ApplicationContextType: ApplicationContextTrait + CatchPanicApplicationContext,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+    NextReturnType: RequestHandlerReturnTrait,
+>(
+    next: impl RequestHandlerFn<ApplicationContextType, RequestContextType, NextReturnType>,
+    req: Request,
+    app_context: Arc<ApplicationContextType>,
+    request_context: RequestContextType,
+) -> Result<Response, ErrorResponse> {
+    let request_line = format!("{} {}", req.method(), req.uri());
+
+    let next_app_context = app_context.clone();
+    let next_task = async move { next(req, next_app_context, request_context).await };
+
+    match CatchUnwind::new(Box::pin(next_task)).await {
+        Ok(ret) => ret,
+        Err(payload) => {
+            log::error!(
+                "Request handler panicked, request = {request_line}, panic = {}",
+                panic_message(payload.as_ref())
+            );
+            Err(ErrorResponse(app_context.panic_response()))
+        }
+    }
+}
+
+impl<
+        T: Deref<Target = CatchPanicApplicationContextType>,
+        CatchPanicApplicationContextType: CatchPanicApplicationContext,
+    > CatchPanicApplicationContext for T
+{
+    fn panic_response(&self) -> Response {
+        self.deref().panic_response()
+    }
+}
@@ -5,7 +5,6 @@ use cookie::time::OffsetDateTime;
 use crate::{
     application_context_trait::ApplicationContextTrait,
     content_type::ContentType,
-    prelude::ResultInspector,
     request_context_trait::RequestContextTrait,
     request_handler::{
         ErrorResponse, Request, RequestHandlerFn, RequestHandlerReturnTrait, Response,
@@ -1,8 +1,10 @@
+mod catch_panic;
 mod debug_log_cookies;
 mod debug_log_headers;
 mod debug_log_request_line;
 mod httponly_header_authorization;
 
+pub use catch_panic::*;
 pub use debug_log_cookies::*;
 pub use debug_log_headers::*;
 pub use debug_log_request_line::*;
@@ -0,0 +1,75 @@
+use std::fmt::Display;
+
+use crate::{
+    body_ext::BodyExt, content_type::ContentType, request_handler::ErrorResponse,
+    response::create_string_response,
+};
+
+/// The default maximum size of a buffered request body, 2 MiB.
+pub const DEFAULT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum BodyError {
+    /// The body is larger than the limit, in bytes.
+    TooLarge { limit: usize },
+    /// The body could not be received, e.g. the client closed the connection.
+    Read(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Display for BodyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BodyError::TooLarge { limit } => {
+                write!(f, "request body is larger than {limit} bytes")
+            }
+            BodyError::Read(e) => write!(f, "could not read request body, error = {e}"),
+        }
+    }
+}
+
+impl std::error::Error for BodyError {}
+
+impl From<BodyError> for ErrorResponse {
+    fn from(e: BodyError) -> Self {
+        let status = match e {
+            BodyError::TooLarge { .. } => hyper::StatusCode::PAYLOAD_TOO_LARGE,
+            BodyError::Read(_) => hyper::StatusCode::BAD_REQUEST,
+        };
+        create_string_response(status, e, ContentType::TextPlain).into()
+    }
+}
+
+/// Buffers the whole body, failing as soon as it gets larger than `limit` bytes. Bodies
+/// announcing a larger `Content-Length` are rejected without reading them.
+pub async fn read_body<BodyType, FrameDataType>(
+    body: BodyType,
+    limit: usize,
+) -> Result<Vec<u8>, BodyError>
+where
+    FrameDataType: hyper::body::Buf + Unpin,
+    BodyType: hyper::body::Body<Data = FrameDataType> + Unpin,
+    BodyType::Error: std::error::Error + Send + Sync + 'static,
+{
+    if body.size_hint().lower() > limit as u64 {
+        return Err(BodyError::TooLarge { limit });
+    }
+
+    let mut data = Vec::new();
+    let mut frames = body.frame_iter();
+    while let Some(frame) = frames.next_frame().await {
+        let frame = frame.map_err(|e| BodyError::Read(Box::new(e)))?;
+        if let Ok(mut frame_data) = frame.into_data() {
+            if data.len() + frame_data.remaining() > limit {
+                return Err(BodyError::TooLarge { limit });
+            }
+            while frame_data.has_remaining() {
+                let chunk = frame_data.chunk();
+                data.extend_from_slice(chunk);
+                let chunk_len = chunk.len();
+                frame_data.advance(chunk_len);
+            }
+        }
+    }
+
+    Ok(data)
+}
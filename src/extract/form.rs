@@ -0,0 +1,508 @@
+use std::{collections::HashMap, fmt::Display};
+
+use hyper::{header::CONTENT_TYPE, HeaderMap};
+use serde::{
+    de::{
+        value::{MapDeserializer, SeqDeserializer, StringDeserializer},
+        DeserializeOwned, IntoDeserializer, Visitor,
+    },
+    forward_to_deserialize_any, Deserializer,
+};
+
+use crate::{
+    content_type::ContentType,
+    request_handler::{ErrorResponse, Request},
+    response::create_string_response,
+};
+
+use super::body::{read_body, BodyError, DEFAULT_BODY_LIMIT};
+
+#[derive(Debug)]
+pub enum FormError {
+    Body(BodyError),
+    /// The `Content-Type` of the request, if it has one, is not
+    /// `application/x-www-form-urlencoded`.
+    UnsupportedContentType(Option<String>),
+    /// The data does not fit the type it is deserialized into.
+    Invalid {
+        /// The field that failed, e.g. `ids[1]`, `None` if the error is not about a single
+        /// field like a missing one.
+        field: Option<String>,
+        message: String,
+    },
+}
+
+impl Display for FormError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormError::Body(e) => e.fmt(f),
+            FormError::UnsupportedContentType(Some(content_type)) => {
+                write!(f, "expected a form request body, got '{content_type}'")
+            }
+            FormError::UnsupportedContentType(None) => {
+                write!(f, "expected a form request body, got no Content-Type")
+            }
+            FormError::Invalid {
+                field: Some(field),
+                message,
+            } => write!(f, "invalid value for field '{field}': {message}"),
+            FormError::Invalid {
+                field: None,
+                message,
+            } => write!(f, "invalid form data: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for FormError {}
+
+impl From<BodyError> for FormError {
+    fn from(e: BodyError) -> Self {
+        Self::Body(e)
+    }
+}
+
+impl From<FormError> for ErrorResponse {
+    fn from(e: FormError) -> Self {
+        let status = match e {
+            FormError::Body(e) => return e.into(),
+            FormError::UnsupportedContentType(_) => hyper::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            FormError::Invalid { .. } => hyper::StatusCode::BAD_REQUEST,
+        };
+        create_string_response(status, e, ContentType::TextPlain).into()
+    }
+}
+
+/// Deserializes the query string of the request, e.g.
+/// `let search: Search = parse_query(&req)?;`, see [`parse_urlencoded`].
+pub fn parse_query<T: DeserializeOwned, BodyType>(
+    req: &hyper::Request<BodyType>,
+) -> Result<T, FormError> {
+    parse_urlencoded(req.uri().query().unwrap_or_default())
+}
+
+/// Deserializes `application/x-www-form-urlencoded` data like `a=1&b=hello+world`.
+///
+/// Repeated keys deserialize into sequences like `Vec<u32>`, missing or empty values into
+/// `None` for `Option` fields. Scalars are parsed from their text, unit enum variants are
+/// matched by name.
+pub fn parse_urlencoded<T: DeserializeOwned>(input: &str) -> Result<T, FormError> {
+    let mut fields: Vec<(String, Vec<String>)> = Vec::new();
+    let mut indices: HashMap<String, usize> = HashMap::new();
+    for (name, value) in form_urlencoded_pairs(input) {
+        match indices.get(&name) {
+            Some(&index) => fields[index].1.push(value),
+            None => {
+                indices.insert(name.clone(), fields.len());
+                fields.push((name, vec![value]));
+            }
+        }
+    }
+
+    serde_path_to_error::deserialize(FormDeserializer(fields)).map_err(|e| {
+        let path = e.path().to_string();
+        FormError::Invalid {
+            field: (path != ".").then_some(path),
+            message: e.into_inner().0,
+        }
+    })
+}
+
+/// Deserializes `application/x-www-form-urlencoded` request bodies, e.g.
+/// `let login: Login = FormBody::new().extract(req).await?;`, see [`parse_urlencoded`].
+///
+/// The body is buffered up to the limit, [`DEFAULT_BODY_LIMIT`] by default.
+#[derive(Clone, Debug)]
+pub struct FormBody {
+    limit: usize,
+}
+
+impl Default for FormBody {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormBody {
+    pub fn new() -> Self {
+        Self {
+            limit: DEFAULT_BODY_LIMIT,
+        }
+    }
+
+    /// The maximum size of the body in bytes, larger bodies fail with
+    /// `413 Payload Too Large`.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub async fn extract<T: DeserializeOwned>(&self, req: Request) -> Result<T, FormError> {
+        let (parts, body) = req.into_parts();
+        self.extract_from(&parts.headers, body).await
+    }
+
+    /// Like [`FormBody::extract`], for requests already split into parts.
+    pub async fn extract_from<T, BodyType, FrameDataType>(
+        &self,
+        headers: &HeaderMap,
+        body: BodyType,
+    ) -> Result<T, FormError>
+    where
+        T: DeserializeOwned,
+        FrameDataType: hyper::body::Buf + Unpin,
+        BodyType: hyper::body::Body<Data = FrameDataType> + Unpin,
+        BodyType::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .map(|content_type| String::from_utf8_lossy(content_type.as_bytes()).into_owned());
+        if !content_type.as_deref().is_some_and(is_form) {
+            return Err(FormError::UnsupportedContentType(content_type));
+        }
+
+        let data = read_body(body, self.limit).await?;
+
+        parse_urlencoded(&String::from_utf8_lossy(&data))
+    }
+}
+
+fn is_form(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+}
+
+/// Splits `a=1&b=hello+world` into decoded name and value pairs, keys without `=` get an empty
+/// value.
+pub(crate) fn form_urlencoded_pairs(query: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode_component(name), decode_component(value))
+        })
+}
+
+fn decode_component(component: &str) -> String {
+    let component = component.replace('+', " ");
+    percent_encoding::percent_decode_str(&component)
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+#[derive(Debug)]
+struct DeError(String);
+
+impl Display for DeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for DeError {}
+
+impl serde::de::Error for DeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+/// The whole form, a map from the names to all of their values.
+struct FormDeserializer(Vec<(String, Vec<String>)>);
+
+impl<'de> Deserializer<'de> for FormDeserializer {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        MapDeserializer::new(
+            self.0
+                .into_iter()
+                .map(|(name, values)| (name, ValuesDeserializer(values))),
+        )
+        .deserialize_any(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+/// All values of one name, a sequence or a single value.
+struct ValuesDeserializer(Vec<String>);
+
+impl ValuesDeserializer {
+    fn single(mut self) -> Result<ValueDeserializer, DeError> {
+        match self.0.len() {
+            1 => Ok(ValueDeserializer(self.0.remove(0))),
+            count => Err(DeError(format!("expected a single value, got {count}"))),
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DeError> for ValuesDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! forward_to_single_value {
+    ($($method:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+            self.single()?.$method(visitor)
+        }
+    )*};
+}
+
+impl<'de> Deserializer<'de> for ValuesDeserializer {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        if self.0.len() == 1 {
+            self.single()?.deserialize_any(visitor)
+        } else {
+            self.deserialize_seq(visitor)
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        if self.0.iter().all(String::is_empty) {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        SeqDeserializer::new(self.0.into_iter().map(ValueDeserializer)).deserialize_any(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.single()?.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.single()?.deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_single_value! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf deserialize_unit
+        deserialize_map deserialize_identifier deserialize_ignored_any
+    }
+}
+
+/// A single value, scalars are parsed from its text.
+struct ValueDeserializer(String);
+
+impl<'de> IntoDeserializer<'de, DeError> for ValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! parse_value {
+    ($($method:ident => $visit:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+            match self.0.parse() {
+                Ok(value) => visitor.$visit(value),
+                Err(e) => Err(DeError(format!("cannot parse '{}', {e}", self.0))),
+            }
+        }
+    )*};
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_string(self.0)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        let variant: StringDeserializer<DeError> = self.0.into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    parse_value! {
+        deserialize_bool => visit_bool
+        deserialize_i8 => visit_i8
+        deserialize_i16 => visit_i16
+        deserialize_i32 => visit_i32
+        deserialize_i64 => visit_i64
+        deserialize_i128 => visit_i128
+        deserialize_u8 => visit_u8
+        deserialize_u16 => visit_u16
+        deserialize_u32 => visit_u32
+        deserialize_u64 => visit_u64
+        deserialize_u128 => visit_u128
+        deserialize_f32 => visit_f32
+        deserialize_f64 => visit_f64
+        deserialize_char => visit_char
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit_struct seq tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Order {
+        Asc,
+        Desc,
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Search {
+        q: String,
+        page: Option<u32>,
+        #[serde(default)]
+        tags: Vec<String>,
+        order: Option<Order>,
+        exact: bool,
+    }
+
+    #[test]
+    fn query_pairs() {
+        let pairs: Vec<_> = form_urlencoded_pairs("a=1&b=hello+world&c&d=%C3%A4").collect();
+
+        assert_eq!(
+            pairs,
+            [
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "hello world".to_string()),
+                ("c".to_string(), String::new()),
+                ("d".to_string(), "ä".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn deserialize_struct() {
+        let search: Search =
+            parse_urlencoded("q=a+b%2Bc&tags=x&page=&tags=y%20z&order=desc&exact=true").unwrap();
+        assert_eq!(
+            search,
+            Search {
+                q: "a b+c".to_string(),
+                page: None,
+                tags: vec!["x".to_string(), "y z".to_string()],
+                order: Some(Order::Desc),
+                exact: true,
+            }
+        );
+
+        let search: Search = parse_urlencoded("q=&page=3&exact=false").unwrap();
+        assert_eq!((search.q.as_str(), search.page), ("", Some(3)));
+        assert!(search.tags.is_empty());
+
+        let all: BTreeMap<String, Vec<String>> = parse_urlencoded("b=2&a=1&b=3").unwrap();
+        assert_eq!(all["b"], ["2", "3"]);
+    }
+
+    #[test]
+    fn failed_field() {
+        let e = parse_urlencoded::<Search>("q=x&page=two&exact=true").unwrap_err();
+        assert!(
+            matches!(&e, FormError::Invalid { field: Some(field), .. } if field == "page"),
+            "{e}"
+        );
+
+        let e = parse_urlencoded::<Search>("q=x&q=y&exact=true").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "invalid value for field 'q': expected a single value, got 2"
+        );
+
+        let e = parse_urlencoded::<Search>("q=x").unwrap_err();
+        assert_eq!(e.to_string(), "invalid form data: missing field `exact`");
+    }
+}
@@ -0,0 +1,222 @@
+use std::fmt::Display;
+
+use hyper::{header::CONTENT_TYPE, HeaderMap};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    content_type::ContentType,
+    request_handler::{ErrorResponse, Request},
+    response::create_string_response,
+};
+
+use super::body::{read_body, BodyError, DEFAULT_BODY_LIMIT};
+
+#[derive(Debug)]
+pub enum JsonError {
+    Body(BodyError),
+    /// The `Content-Type` of the request, if it has one, is not JSON.
+    UnsupportedContentType(Option<String>),
+    /// The body is not valid JSON, or it does not fit the type it is deserialized into.
+    Invalid {
+        line: usize,
+        column: usize,
+        /// Where the error is in the document, e.g. `items[2].id`, `.` for the root.
+        path: String,
+        message: String,
+    },
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Body(e) => e.fmt(f),
+            JsonError::UnsupportedContentType(Some(content_type)) => {
+                write!(f, "expected a JSON request body, got '{content_type}'")
+            }
+            JsonError::UnsupportedContentType(None) => {
+                write!(f, "expected a JSON request body, got no Content-Type")
+            }
+            JsonError::Invalid {
+                line,
+                column,
+                path,
+                message,
+            } => write!(
+                f,
+                "invalid JSON at line {line}, column {column}, path '{path}': {message}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl From<BodyError> for JsonError {
+    fn from(e: BodyError) -> Self {
+        Self::Body(e)
+    }
+}
+
+impl From<JsonError> for ErrorResponse {
+    fn from(e: JsonError) -> Self {
+        let status = match e {
+            JsonError::Body(e) => return e.into(),
+            JsonError::UnsupportedContentType(_) => hyper::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            JsonError::Invalid { .. } => hyper::StatusCode::BAD_REQUEST,
+        };
+        create_string_response(status, e, ContentType::TextPlain).into()
+    }
+}
+
+/// Deserializes JSON request bodies, e.g.
+/// `let user: NewUser = JsonBody::new().limit(64 * 1024).extract(req).await?;`.
+///
+/// The `Content-Type` has to be `application/json` or `application/*+json`, and the body is
+/// buffered up to the limit, [`DEFAULT_BODY_LIMIT`] by default.
+#[derive(Clone, Debug)]
+pub struct JsonBody {
+    limit: usize,
+}
+
+impl Default for JsonBody {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonBody {
+    pub fn new() -> Self {
+        Self {
+            limit: DEFAULT_BODY_LIMIT,
+        }
+    }
+
+    /// The maximum size of the body in bytes, larger bodies fail with
+    /// `413 Payload Too Large`.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub async fn extract<T: DeserializeOwned>(&self, req: Request) -> Result<T, JsonError> {
+        let (parts, body) = req.into_parts();
+        self.extract_from(&parts.headers, body).await
+    }
+
+    /// Like [`JsonBody::extract`], for requests already split into parts.
+    pub async fn extract_from<T, BodyType, FrameDataType>(
+        &self,
+        headers: &HeaderMap,
+        body: BodyType,
+    ) -> Result<T, JsonError>
+    where
+        T: DeserializeOwned,
+        FrameDataType: hyper::body::Buf + Unpin,
+        BodyType: hyper::body::Body<Data = FrameDataType> + Unpin,
+        BodyType::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .map(|content_type| String::from_utf8_lossy(content_type.as_bytes()).into_owned());
+        if !content_type.as_deref().is_some_and(is_json) {
+            return Err(JsonError::UnsupportedContentType(content_type));
+        }
+
+        let data = read_body(body, self.limit).await?;
+
+        parse(&data)
+    }
+}
+
+fn is_json(content_type: &str) -> bool {
+    let media_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    media_type == "application/json"
+        || media_type
+            .strip_prefix("application/")
+            .is_some_and(|subtype| subtype.ends_with("+json"))
+}
+
+fn parse<T: DeserializeOwned>(data: &[u8]) -> Result<T, JsonError> {
+    let mut deserializer = serde_json::Deserializer::from_slice(data);
+    let value = serde_path_to_error::deserialize(&mut deserializer)
+        .map_err(|e| invalid_json(e.inner(), e.path().to_string()))?;
+    deserializer
+        .end()
+        .map_err(|e| invalid_json(&e, ".".to_string()))?;
+
+    Ok(value)
+}
+
+fn invalid_json(e: &serde_json::Error, path: String) -> JsonError {
+    JsonError::Invalid {
+        line: e.line(),
+        column: e.column(),
+        path,
+        message: strip_location(&e.to_string()),
+    }
+}
+
+/// serde_json appends the location to its messages, it is reported separately.
+fn strip_location(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Order {
+        #[allow(dead_code)]
+        items: Vec<Item>,
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Item {
+        #[allow(dead_code)]
+        id: u64,
+    }
+
+    #[test]
+    fn json_content_types() {
+        assert!(is_json("application/json"));
+        assert!(is_json("Application/JSON; charset=utf-8"));
+        assert!(is_json("application/vnd.x.v2+json"));
+        assert!(!is_json("text/plain"));
+        assert!(!is_json("application/jsonp"));
+    }
+
+    #[test]
+    fn error_location() {
+        let e = parse::<Order>(b"{\n  \"items\": [{\"id\": 1}, {\"id\": \"two\"}]\n}").unwrap_err();
+        let JsonError::Invalid {
+            line,
+            column,
+            path,
+            message,
+        } = e
+        else {
+            panic!("unexpected error {e}");
+        };
+        assert_eq!((line, column), (2, 35));
+        assert_eq!(path, "items[1].id");
+        assert!(
+            message.starts_with("invalid type: string \"two\""),
+            "{message}"
+        );
+
+        assert!(matches!(
+            parse::<Order>(b"{\"items\": []} trailing"),
+            Err(JsonError::Invalid { line: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,11 @@
+mod body;
+mod form;
+mod json;
+mod multipart;
+
+pub use body::{read_body, BodyError, DEFAULT_BODY_LIMIT};
+pub use form::{parse_query, parse_urlencoded, FormBody, FormError};
+pub use json::{JsonBody, JsonError};
+pub use multipart::{Multipart, MultipartError, MultipartLimits, Part};
+
+pub(crate) use form::form_urlencoded_pairs;
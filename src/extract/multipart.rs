@@ -0,0 +1,613 @@
+use std::{fmt::Display, path::Path};
+
+use hyper::{
+    header::{HeaderName, HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE},
+    HeaderMap,
+};
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    body_ext::{frame_iter::FrameIter, BodyExt},
+    content_type::ContentType,
+    request_handler::{ErrorResponse, Request},
+    response::create_string_response,
+};
+
+use super::body::BodyError;
+
+/// Limits of a [`Multipart`] body, in bytes.
+#[derive(Clone, Debug)]
+pub struct MultipartLimits {
+    /// The whole body, 64 MiB by default.
+    pub total_size: usize,
+    /// The data of a single part, 16 MiB by default.
+    pub part_size: usize,
+    /// The headers of a single part, 8 KiB by default.
+    pub headers_size: usize,
+}
+
+impl Default for MultipartLimits {
+    fn default() -> Self {
+        Self {
+            total_size: 64 * 1024 * 1024,
+            part_size: 16 * 1024 * 1024,
+            headers_size: 8 * 1024,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MultipartError {
+    /// The `Content-Type` of the request is not `multipart/form-data` with a boundary.
+    InvalidContentType,
+    Body(BodyError),
+    PartTooLarge {
+        name: Option<String>,
+        limit: usize,
+    },
+    HeadersTooLarge {
+        limit: usize,
+    },
+    /// The body is not valid `multipart/form-data`, e.g. it ends in the middle of a part.
+    Malformed(&'static str),
+    /// The data of a part could not be written, see [`Part::save_to`].
+    Io(std::io::Error),
+}
+
+impl Display for MultipartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MultipartError::InvalidContentType => {
+                write!(
+                    f,
+                    "expected a multipart/form-data request body with a boundary"
+                )
+            }
+            MultipartError::Body(e) => e.fmt(f),
+            MultipartError::PartTooLarge {
+                name: Some(name),
+                limit,
+            } => write!(f, "part '{name}' is larger than {limit} bytes"),
+            MultipartError::PartTooLarge { name: None, limit } => {
+                write!(f, "part is larger than {limit} bytes")
+            }
+            MultipartError::HeadersTooLarge { limit } => {
+                write!(f, "part headers are larger than {limit} bytes")
+            }
+            MultipartError::Malformed(reason) => write!(f, "malformed multipart body: {reason}"),
+            MultipartError::Io(e) => write!(f, "could not save part, error = {e}"),
+        }
+    }
+}
+
+impl std::error::Error for MultipartError {}
+
+impl From<BodyError> for MultipartError {
+    fn from(e: BodyError) -> Self {
+        Self::Body(e)
+    }
+}
+
+impl From<std::io::Error> for MultipartError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<MultipartError> for ErrorResponse {
+    fn from(e: MultipartError) -> Self {
+        let status = match e {
+            MultipartError::InvalidContentType => hyper::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            MultipartError::Body(e) => return e.into(),
+            MultipartError::PartTooLarge { .. } | MultipartError::HeadersTooLarge { .. } => {
+                hyper::StatusCode::PAYLOAD_TOO_LARGE
+            }
+            MultipartError::Malformed(_) => hyper::StatusCode::BAD_REQUEST,
+            MultipartError::Io(_) => hyper::StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        create_string_response(status, e, ContentType::TextPlain).into()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Before the first delimiter.
+    Preamble,
+    /// After a delimiter, before the headers of a part.
+    Headers,
+    /// In the data of the current part.
+    Data,
+    /// After the close delimiter.
+    End,
+}
+
+/// Streaming `multipart/form-data` parser, the parts are read one after the other as the body
+/// arrives, without buffering the whole body, e.g.
+/// `while let Some(part) = multipart.next_part().await? { part.save_to(path).await?; }`.
+pub struct Multipart<BodyType, FrameDataType>
+where
+    FrameDataType: hyper::body::Buf,
+    BodyType: hyper::body::Body<Data = FrameDataType> + Unpin,
+{
+    frames: FrameIter<BodyType, FrameDataType>,
+    /// `\r\n--boundary`, the first delimiter may come without the line break.
+    delimiter: Vec<u8>,
+    limits: MultipartLimits,
+    buffer: Vec<u8>,
+    body_finished: bool,
+    total_size: usize,
+    state: State,
+    /// The name and the data size of the current part.
+    part_name: Option<String>,
+    part_size: usize,
+}
+
+impl Multipart<hyper::body::Incoming, hyper::body::Bytes> {
+    /// Fails if the `Content-Type` is not `multipart/form-data` with a boundary.
+    pub fn from_request(req: Request) -> Result<Self, MultipartError> {
+        let (parts, body) = req.into_parts();
+        let boundary = parse_boundary(&parts.headers).ok_or(MultipartError::InvalidContentType)?;
+
+        Ok(Self::new(body, &boundary))
+    }
+}
+
+impl<BodyType, FrameDataType> Multipart<BodyType, FrameDataType>
+where
+    FrameDataType: hyper::body::Buf + Unpin,
+    BodyType: hyper::body::Body<Data = FrameDataType> + Unpin,
+    BodyType::Error: std::error::Error + Send + Sync + 'static,
+{
+    pub fn new(body: BodyType, boundary: &str) -> Self {
+        Self {
+            frames: body.frame_iter(),
+            delimiter: [b"\r\n--", boundary.as_bytes()].concat(),
+            limits: MultipartLimits::default(),
+            buffer: Vec::new(),
+            body_finished: false,
+            total_size: 0,
+            state: State::Preamble,
+            part_name: None,
+            part_size: 0,
+        }
+    }
+
+    pub fn limits(mut self, limits: MultipartLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// The next part, the rest of the data of the previous part is skipped.
+    pub async fn next_part(
+        &mut self,
+    ) -> Result<Option<Part<'_, BodyType, FrameDataType>>, MultipartError> {
+        while self.state == State::Data {
+            self.next_chunk().await?;
+        }
+
+        if self.state == State::Preamble {
+            self.skip_preamble().await?;
+        }
+        if self.state == State::End {
+            return Ok(None);
+        }
+
+        let headers = self.read_headers().await?;
+        let (name, file_name) = headers
+            .get(CONTENT_DISPOSITION)
+            .map(parse_content_disposition)
+            .unwrap_or_default();
+        self.state = State::Data;
+        self.part_name = name.clone();
+        self.part_size = 0;
+
+        Ok(Some(Part {
+            multipart: self,
+            headers,
+            name,
+            file_name,
+        }))
+    }
+
+    /// Appends the next data frame of the body to the buffer, `false` at the end of the body.
+    async fn fill_buffer(&mut self) -> Result<bool, MultipartError> {
+        while !self.body_finished {
+            match self.frames.next_frame().await {
+                Some(frame) => {
+                    let frame = frame.map_err(|e| BodyError::Read(Box::new(e)))?;
+                    let Ok(mut data) = frame.into_data() else {
+                        continue;
+                    };
+                    self.total_size += data.remaining();
+                    if self.total_size > self.limits.total_size {
+                        return Err(BodyError::TooLarge {
+                            limit: self.limits.total_size,
+                        }
+                        .into());
+                    }
+                    while data.has_remaining() {
+                        let chunk_len = data.chunk().len();
+                        self.buffer.extend_from_slice(data.chunk());
+                        data.advance(chunk_len);
+                    }
+                    return Ok(true);
+                }
+                None => self.body_finished = true,
+            }
+        }
+
+        Ok(false)
+    }
+
+    async fn skip_preamble(&mut self) -> Result<(), MultipartError> {
+        // the body may start with the delimiter, without the line break before it
+        let delimiter = self.delimiter[2..].to_vec();
+        loop {
+            if self.buffer.starts_with(&delimiter) {
+                self.buffer.drain(..delimiter.len());
+                return self.after_delimiter().await;
+            }
+            if let Some(index) = find(&self.buffer, &self.delimiter) {
+                self.buffer.drain(..index + self.delimiter.len());
+                return self.after_delimiter().await;
+            }
+
+            let keep = self.delimiter.len().min(self.buffer.len());
+            self.buffer.drain(..self.buffer.len() - keep);
+            if !self.fill_buffer().await? {
+                return Err(MultipartError::Malformed("no boundary in the body"));
+            }
+        }
+    }
+
+    /// Decides whether a part or the end of the body follows the delimiter just consumed.
+    async fn after_delimiter(&mut self) -> Result<(), MultipartError> {
+        while self.buffer.len() < 2 {
+            if !self.fill_buffer().await? {
+                return Err(MultipartError::Malformed("body ends after a boundary"));
+            }
+        }
+
+        if self.buffer.starts_with(b"--") {
+            self.state = State::End;
+            return Ok(());
+        }
+
+        // transport padding may follow the delimiter before the line break
+        loop {
+            if let Some(index) = find(&self.buffer, b"\r\n") {
+                if self.buffer[..index]
+                    .iter()
+                    .any(|c| *c != b' ' && *c != b'\t')
+                {
+                    return Err(MultipartError::Malformed(
+                        "unexpected data after a boundary",
+                    ));
+                }
+                self.buffer.drain(..index + 2);
+                self.state = State::Headers;
+                return Ok(());
+            }
+            if self.buffer.len() > self.limits.headers_size {
+                return Err(MultipartError::Malformed(
+                    "unexpected data after a boundary",
+                ));
+            }
+            if !self.fill_buffer().await? {
+                return Err(MultipartError::Malformed("body ends after a boundary"));
+            }
+        }
+    }
+
+    async fn read_headers(&mut self) -> Result<HeaderMap, MultipartError> {
+        let end = loop {
+            if self.buffer.starts_with(b"\r\n") {
+                break 0;
+            }
+            if let Some(index) = find(&self.buffer, b"\r\n\r\n") {
+                break index + 2;
+            }
+            if self.buffer.len() > self.limits.headers_size {
+                return Err(MultipartError::HeadersTooLarge {
+                    limit: self.limits.headers_size,
+                });
+            }
+            if !self.fill_buffer().await? {
+                return Err(MultipartError::Malformed(
+                    "body ends in the headers of a part",
+                ));
+            }
+        };
+        if end > self.limits.headers_size {
+            return Err(MultipartError::HeadersTooLarge {
+                limit: self.limits.headers_size,
+            });
+        }
+
+        let mut headers = HeaderMap::new();
+        for line in self.buffer[..end].split(|c| *c == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.is_empty() {
+                continue;
+            }
+            let colon = line
+                .iter()
+                .position(|c| *c == b':')
+                .ok_or(MultipartError::Malformed("invalid part header"))?;
+            let name = HeaderName::from_bytes(&line[..colon])
+                .map_err(|_| MultipartError::Malformed("invalid part header name"))?;
+            let value = HeaderValue::from_bytes(line[colon + 1..].trim_ascii())
+                .map_err(|_| MultipartError::Malformed("invalid part header value"))?;
+            headers.append(name, value);
+        }
+        self.buffer.drain(..end + 2);
+
+        Ok(headers)
+    }
+
+    /// The next chunk of the data of the current part, `None` at its end.
+    async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, MultipartError> {
+        if self.state != State::Data {
+            return Ok(None);
+        }
+
+        loop {
+            let (chunk_len, at_delimiter) = match find(&self.buffer, &self.delimiter) {
+                Some(index) => (index, true),
+                // the end of the buffer may be the start of the delimiter
+                None => (
+                    self.buffer.len().saturating_sub(self.delimiter.len() - 1),
+                    false,
+                ),
+            };
+
+            if chunk_len > 0 || at_delimiter {
+                self.part_size += chunk_len;
+                if self.part_size > self.limits.part_size {
+                    return Err(MultipartError::PartTooLarge {
+                        name: self.part_name.clone(),
+                        limit: self.limits.part_size,
+                    });
+                }
+
+                let chunk: Vec<u8> = self.buffer.drain(..chunk_len).collect();
+                if at_delimiter {
+                    self.buffer.drain(..self.delimiter.len());
+                    self.after_delimiter().await?;
+                }
+                if !chunk.is_empty() {
+                    return Ok(Some(chunk));
+                }
+                if at_delimiter {
+                    return Ok(None);
+                }
+            }
+
+            if !self.fill_buffer().await? {
+                return Err(MultipartError::Malformed("body ends in the data of a part"));
+            }
+        }
+    }
+}
+
+/// A part of a [`Multipart`] body, its data is read with [`Part::chunk`] or one of the
+/// methods consuming the part.
+pub struct Part<'a, BodyType, FrameDataType>
+where
+    FrameDataType: hyper::body::Buf,
+    BodyType: hyper::body::Body<Data = FrameDataType> + Unpin,
+{
+    multipart: &'a mut Multipart<BodyType, FrameDataType>,
+    headers: HeaderMap,
+    name: Option<String>,
+    file_name: Option<String>,
+}
+
+impl<BodyType, FrameDataType> Part<'_, BodyType, FrameDataType>
+where
+    FrameDataType: hyper::body::Buf + Unpin,
+    BodyType: hyper::body::Body<Data = FrameDataType> + Unpin,
+    BodyType::Error: std::error::Error + Send + Sync + 'static,
+{
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The `name` of the `Content-Disposition` header, the name of the form field.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The `filename` of the `Content-Disposition` header, only file parts have one.
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+    }
+
+    /// The next chunk of the data of the part, `None` at its end.
+    pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>, MultipartError> {
+        self.multipart.next_chunk().await
+    }
+
+    pub async fn bytes(mut self) -> Result<Vec<u8>, MultipartError> {
+        let mut data = Vec::new();
+        while let Some(chunk) = self.chunk().await? {
+            data.extend(chunk);
+        }
+        Ok(data)
+    }
+
+    pub async fn text(self) -> Result<String, MultipartError> {
+        String::from_utf8(self.bytes().await?)
+            .map_err(|_| MultipartError::Malformed("part is not valid UTF-8"))
+    }
+
+    /// Streams the data of the part into a new file at `path`, and returns its size. The file
+    /// is removed if the part cannot be read completely.
+    pub async fn save_to(mut self, path: impl AsRef<Path>) -> Result<u64, MultipartError> {
+        let path = path.as_ref();
+        let mut file = tokio::fs::File::create(path).await?;
+
+        let write = async {
+            let mut size = 0;
+            while let Some(chunk) = self.chunk().await? {
+                file.write_all(&chunk).await?;
+                size += chunk.len() as u64;
+            }
+            file.flush().await?;
+            Ok::<_, MultipartError>(size)
+        };
+
+        match write.await {
+            Ok(size) => Ok(size),
+            Err(e) => {
+                let _ = tokio::fs::remove_file(path).await;
+                Err(e)
+            }
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// The boundary of a `multipart/form-data` `Content-Type`.
+fn parse_boundary(headers: &HeaderMap) -> Option<String> {
+    let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    let (media_type, params) = content_type.split_once(';')?;
+    if !media_type
+        .trim()
+        .eq_ignore_ascii_case("multipart/form-data")
+    {
+        return None;
+    }
+
+    parse_params(params)
+        .into_iter()
+        .find(|(name, _)| name == "boundary")
+        .map(|(_, boundary)| boundary)
+        .filter(|boundary| !boundary.is_empty() && boundary.len() <= 70)
+}
+
+/// The `name` and `filename` of a `Content-Disposition` header, `filename*` takes priority.
+fn parse_content_disposition(value: &HeaderValue) -> (Option<String>, Option<String>) {
+    let value = String::from_utf8_lossy(value.as_bytes());
+    let Some((_, params)) = value.split_once(';') else {
+        return (None, None);
+    };
+
+    let mut name = None;
+    let mut file_name = None;
+    let mut extended_file_name = None;
+    for (param_name, param_value) in parse_params(params) {
+        match param_name.as_str() {
+            "name" => name = Some(param_value),
+            "filename" => file_name = Some(param_value),
+            "filename*" => {
+                extended_file_name = param_value
+                    .split_once("''")
+                    .filter(|(charset, _)| charset.eq_ignore_ascii_case("utf-8"))
+                    .and_then(|(_, encoded)| {
+                        percent_encoding::percent_decode_str(encoded)
+                            .decode_utf8()
+                            .ok()
+                            .map(|decoded| decoded.into_owned())
+                    })
+            }
+            _ => {}
+        }
+    }
+
+    (name, extended_file_name.or(file_name))
+}
+
+/// The `name=value` parameters of a header, values may be quoted, names are lowercased.
+fn parse_params(params: &str) -> Vec<(String, String)> {
+    let mut parsed = Vec::new();
+    let mut chars = params.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ';').is_some() {}
+
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=' && *c != ';') {
+            name.push(c);
+        }
+        if name.is_empty() {
+            return parsed;
+        }
+
+        let mut value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            if chars.next_if_eq(&'"').is_some() {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => value.extend(chars.next()),
+                        c => value.push(c),
+                    }
+                }
+            } else {
+                while let Some(c) = chars.next_if(|c| *c != ';') {
+                    value.push(c);
+                }
+                value = value.trim_end().to_string();
+            }
+        }
+
+        parsed.push((name.trim().to_ascii_lowercase(), value));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn boundary() {
+        let headers = |content_type: &'static str| {
+            HeaderMap::from_iter([(CONTENT_TYPE, HeaderValue::from_static(content_type))])
+        };
+
+        assert_eq!(
+            parse_boundary(&headers("multipart/form-data; boundary=----abc")).as_deref(),
+            Some("----abc")
+        );
+        assert_eq!(
+            parse_boundary(&headers(
+                "Multipart/Form-Data; charset=utf-8; boundary=\"a b\""
+            ))
+            .as_deref(),
+            Some("a b")
+        );
+        assert_eq!(parse_boundary(&headers("multipart/form-data")), None);
+        assert_eq!(parse_boundary(&headers("text/plain; boundary=x")), None);
+    }
+
+    #[test]
+    fn content_disposition() {
+        assert_eq!(
+            parse_content_disposition(&HeaderValue::from_static(
+                r#"form-data; name="file"; filename="a \"b\".txt""#
+            )),
+            (Some("file".into()), Some("a \"b\".txt".into()))
+        );
+        assert_eq!(
+            parse_content_disposition(&HeaderValue::from_static(
+                "form-data; name=file; filename=\"a.txt\"; filename*=UTF-8''%C3%A1.txt"
+            )),
+            (Some("file".into()), Some("á.txt".into()))
+        );
+        assert_eq!(
+            parse_content_disposition(&HeaderValue::from_static("form-data")),
+            (None, None)
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use std::{future::Future, sync::Arc};
+
+use hyper::{header::CONTENT_TYPE, http::HeaderValue, HeaderMap, StatusCode};
+
+use crate::{
+    application_context_trait::ApplicationContextTrait,
+    content_type::ContentType,
+    request_context_trait::RequestContextTrait,
+    request_handler::{ErrorResponse, Request, RequestHandlerFn, Response},
+    response::{
+        create_empty_response, create_json_response, create_static_str_response,
+        create_string_response,
+    },
+    response_body::ResponseBody,
+    routing::{PathParams, RouterFnReturnType},
+};
+
+/// Values handlers can return, see [`handler`]. Strings are sent as `text/plain`, byte
+/// vectors as `application/octet-stream`, and the status and headers of a response can be set
+/// with tuples, e.g. `(StatusCode::CREATED, Json(user))`.
+pub trait IntoResponse {
+    fn into_response(self) -> Response;
+
+    /// What the adapted handler returns, errors stay errors so router error handlers and
+    /// decorators see them, see [`crate::routing::RouterBuilder::error_handler`].
+    #[allow(clippy::result_large_err)]
+    fn into_handler_result(self) -> Result<Response, ErrorResponse>
+    where
+        Self: Sized,
+    {
+        Ok(self.into_response())
+    }
+}
+
+impl IntoResponse for Response {
+    fn into_response(self) -> Response {
+        self
+    }
+}
+
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> Response {
+        self.0
+    }
+
+    fn into_handler_result(self) -> Result<Response, ErrorResponse> {
+        Err(self)
+    }
+}
+
+impl IntoResponse for ResponseBody {
+    fn into_response(self) -> Response {
+        Response::new(self)
+    }
+}
+
+/// An empty response with the status.
+impl IntoResponse for StatusCode {
+    fn into_response(self) -> Response {
+        create_empty_response(self)
+    }
+}
+
+impl IntoResponse for &'static str {
+    fn into_response(self) -> Response {
+        create_static_str_response(StatusCode::OK, self, ContentType::TextPlain)
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> Response {
+        create_string_response(StatusCode::OK, self, ContentType::TextPlain)
+    }
+}
+
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> Response {
+        let mut resp = Response::new(self.into());
+        resp.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        );
+        resp
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for (StatusCode, T) {
+    fn into_response(self) -> Response {
+        let (status, value) = self;
+        let mut resp = value.into_response();
+        *resp.status_mut() = status;
+        resp
+    }
+}
+
+/// The headers replace the ones of the value with the same name, e.g. its `Content-Type`.
+impl<T: IntoResponse> IntoResponse for (StatusCode, HeaderMap, T) {
+    fn into_response(self) -> Response {
+        let (status, headers, value) = self;
+        let mut resp = (status, value).into_response();
+        resp.headers_mut().extend(headers);
+        resp
+    }
+}
+
+impl<T: IntoResponse, E: IntoResponse> IntoResponse for Result<T, E> {
+    fn into_response(self) -> Response {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(e) => e.into_response(),
+        }
+    }
+
+    fn into_handler_result(self) -> Result<Response, ErrorResponse> {
+        match self {
+            Ok(value) => Ok(value.into_response()),
+            Err(e) => Err(ErrorResponse(e.into_response())),
+        }
+    }
+}
+
+/// Sends the value as `application/json`, values that fail to serialize are answered with
+/// `500 Internal Server Error`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Json<T>(pub T);
+
+impl<T: serde::Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        create_json_response(StatusCode::OK, &self.0).unwrap_or_else(|e| {
+            log::error!("Could not serialize JSON response, error = {:?}", e);
+            create_empty_response(StatusCode::INTERNAL_SERVER_ERROR)
+        })
+    }
+}
+
+/// Adapts a handler returning any [`IntoResponse`] into a [`RequestHandlerFn`], for
+/// [`crate::routing::RouterBuilder::path`], [`crate::server::run_http1_tcp_server`] and the
+/// decorators, e.g. `run_http1_tcp_server(address, handler(hello), app_context)`.
+pub fn handler<ApplicationContextType, RequestContextType, ReturnType, IntoResponseType>(
+    request_handler: impl Fn(Request, Arc<ApplicationContextType>, RequestContextType) -> ReturnType
+        + Send
+        + Sync
+        + 'static,
+) -> impl RequestHandlerFn<ApplicationContextType, RequestContextType, RouterFnReturnType>
+where
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+    ReturnType: Future<Output = IntoResponseType> + Send + Sync + 'static,
+    IntoResponseType: IntoResponse,
+{
+    move |req, app_context, request_context| {
+        let response = request_handler(req, app_context, request_context);
+        Box::pin(async move { response.await.into_handler_result() }) as RouterFnReturnType
+    }
+}
+
+/// Like [`handler`], for the handlers of [`crate::routing::RouterBuilder::route`].
+pub fn route_handler<ApplicationContextType, RequestContextType, ReturnType, IntoResponseType>(
+    request_handler: impl Fn(Request, Arc<ApplicationContextType>, RequestContextType, PathParams) -> ReturnType
+        + Send
+        + Sync
+        + 'static,
+) -> impl Fn(
+    Request,
+    Arc<ApplicationContextType>,
+    RequestContextType,
+    PathParams,
+) -> RouterFnReturnType
+       + Send
+       + Sync
+       + 'static
+where
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+    ReturnType: Future<Output = IntoResponseType> + Send + Sync + 'static,
+    IntoResponseType: IntoResponse,
+{
+    move |req, app_context, request_context, params| {
+        let response = request_handler(req, app_context, request_context, params);
+        Box::pin(async move { response.await.into_handler_result() })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::header::LOCATION;
+
+    use super::*;
+
+    #[test]
+    fn status_and_headers() {
+        let resp = (StatusCode::CREATED, "created").into_response();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(resp.headers()[CONTENT_TYPE], "text/plain");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(LOCATION, HeaderValue::from_static("/users/1"));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        let resp = (StatusCode::SEE_OTHER, headers, "<p>moved</p>".to_string()).into_response();
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        assert_eq!(resp.headers()[LOCATION], "/users/1");
+        assert_eq!(resp.headers().get_all(CONTENT_TYPE).iter().count(), 1);
+        assert_eq!(resp.headers()[CONTENT_TYPE], "text/html");
+    }
+
+    #[test]
+    fn results() {
+        let ok: Result<Json<u32>, StatusCode> = Ok(Json(1));
+        let resp = ok.into_response();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[CONTENT_TYPE], "application/json");
+
+        let err: Result<Vec<u8>, (StatusCode, &'static str)> = Err((StatusCode::CONFLICT, "taken"));
+        assert!(matches!(
+            err.into_handler_result(),
+            Err(ErrorResponse(resp)) if resp.status() == StatusCode::CONFLICT
+        ));
+    }
+}
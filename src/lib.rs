@@ -8,8 +8,11 @@ pub mod content_type;
 pub mod cookies;
 pub mod decorators;
 pub mod error;
+pub mod extract;
 pub mod filestream;
+pub mod into_response;
 pub mod jwt_manager;
+pub mod negotiation;
 pub mod prelude;
 pub mod request_context_trait;
 pub mod request_handler;
@@ -17,6 +20,7 @@ pub mod response;
 pub mod response_body;
 pub mod routing;
 pub mod server;
+pub mod test_client;
 
 #[cfg(test)]
 mod tests;
@@ -63,8 +67,6 @@ mod test {
         },
     };
 
-    use super::*;
-
     struct ApplicationContext;
 
     impl ApplicationContextTrait for ApplicationContext {}
@@ -99,7 +101,7 @@ mod test {
         app_context: Arc<ApplicationContextType>,
         req_context: RequestContextType,
     ) -> Result<Response, ErrorResponse> {
-        return next(req, app_context, req_context).await;
+        next(req, app_context, req_context).await
     }
 
     #[test]
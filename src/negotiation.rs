@@ -0,0 +1,283 @@
+use std::fmt::Display;
+
+use hyper::{
+    header::{ACCEPT, VARY},
+    http::HeaderValue,
+    HeaderMap,
+};
+
+use crate::{
+    content_type::ContentType, request_handler::ErrorResponse, response::create_string_response,
+};
+
+/// A serialization format for [`crate::response::create_negotiated_response`], the formats
+/// other than JSON are enabled by the `msgpack`, `cbor`, `yaml` and `xml` cargo features.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    /// The root element is named after the serialized type.
+    #[cfg(feature = "xml")]
+    Xml,
+}
+
+impl Format {
+    /// All enabled formats, in the order they are preferred when the client has no preference.
+    pub const ALL: &'static [Format] = &[
+        Format::Json,
+        #[cfg(feature = "msgpack")]
+        Format::MessagePack,
+        #[cfg(feature = "cbor")]
+        Format::Cbor,
+        #[cfg(feature = "yaml")]
+        Format::Yaml,
+        #[cfg(feature = "xml")]
+        Format::Xml,
+    ];
+
+    /// The media type of the serialized data, sent as `Content-Type`.
+    pub fn media_type(self) -> &'static str {
+        self.media_types()[0]
+    }
+
+    /// The media types clients ask for, the registered one first.
+    fn media_types(self) -> &'static [&'static str] {
+        match self {
+            Format::Json => &["application/json"],
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => &["application/msgpack", "application/x-msgpack"],
+            #[cfg(feature = "cbor")]
+            Format::Cbor => &["application/cbor"],
+            #[cfg(feature = "yaml")]
+            Format::Yaml => &["application/yaml", "application/x-yaml", "text/yaml"],
+            #[cfg(feature = "xml")]
+            Format::Xml => &["application/xml", "text/xml"],
+        }
+    }
+
+    pub fn serialize<T: serde::Serialize>(self, data: &T) -> Result<Vec<u8>, NegotiationError> {
+        match self {
+            Format::Json => serde_json::to_vec(data).map_err(NegotiationError::serialize),
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => {
+                rmp_serde::to_vec_named(data).map_err(NegotiationError::serialize)
+            }
+            #[cfg(feature = "cbor")]
+            Format::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(data, &mut bytes).map_err(NegotiationError::serialize)?;
+                Ok(bytes)
+            }
+            #[cfg(feature = "yaml")]
+            Format::Yaml => serde_yaml::to_string(data)
+                .map(String::into_bytes)
+                .map_err(NegotiationError::serialize),
+            #[cfg(feature = "xml")]
+            Format::Xml => quick_xml::se::to_string(data)
+                .map(String::into_bytes)
+                .map_err(NegotiationError::serialize),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NegotiationError {
+    /// The `Accept` header rules out all of the offered formats.
+    NotAcceptable(Vec<Format>),
+    Serialize(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl NegotiationError {
+    fn serialize(e: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Self::Serialize(Box::new(e))
+    }
+}
+
+impl Display for NegotiationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NegotiationError::NotAcceptable(formats) => {
+                let media_types: Vec<_> =
+                    formats.iter().map(|format| format.media_type()).collect();
+                write!(
+                    f,
+                    "none of the available media types is acceptable: {}",
+                    media_types.join(", ")
+                )
+            }
+            NegotiationError::Serialize(e) => {
+                write!(f, "could not serialize response, error = {e}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for NegotiationError {}
+
+impl From<NegotiationError> for ErrorResponse {
+    fn from(e: NegotiationError) -> Self {
+        let status = match e {
+            NegotiationError::NotAcceptable(_) => hyper::StatusCode::NOT_ACCEPTABLE,
+            NegotiationError::Serialize(_) => hyper::StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let mut response = create_string_response(status, e, ContentType::TextPlain);
+        response
+            .headers_mut()
+            .insert(VARY, HeaderValue::from_static("accept"));
+        response.into()
+    }
+}
+
+/// Picks the format the `Accept` headers prefer by quality, each media type is rated by the
+/// most specific range matching it, e.g. `application/*` before `*/*`. Ties go to the format
+/// listed first in `formats`, and so does a request without an `Accept` header. `None` if
+/// every format is ruled out.
+pub fn negotiate(headers: &HeaderMap, formats: &[Format]) -> Option<Format> {
+    let ranges: Vec<_> = headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|accept| accept.to_str().ok())
+        .flat_map(|accept| accept.split(','))
+        .filter_map(MediaRange::parse)
+        .collect();
+    if ranges.is_empty() {
+        return formats.first().copied();
+    }
+
+    let mut best: Option<(Format, f32)> = None;
+    for &format in formats {
+        let quality = format
+            .media_types()
+            .iter()
+            .map(|media_type| quality(&ranges, media_type))
+            .fold(0.0, f32::max);
+        if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((format, quality));
+        }
+    }
+
+    best.map(|(format, _)| format)
+}
+
+fn quality(ranges: &[MediaRange], media_type: &str) -> f32 {
+    ranges
+        .iter()
+        .filter_map(|range| {
+            range
+                .specificity(media_type)
+                .map(|specificity| (specificity, range.quality))
+        })
+        .max_by_key(|(specificity, _)| *specificity)
+        .map_or(0.0, |(_, quality)| quality)
+}
+
+struct MediaRange {
+    /// Lowercase, e.g. `text/*`.
+    essence: String,
+    quality: f32,
+}
+
+impl MediaRange {
+    fn parse(media_range: &str) -> Option<Self> {
+        let mut params = media_range.split(';');
+        let essence = params.next()?.trim().to_ascii_lowercase();
+        if !essence.contains('/') {
+            return None;
+        }
+
+        let mut quality = 1.0;
+        for param in params {
+            if let Some(value) = param.trim().strip_prefix("q=") {
+                quality = value.trim().parse::<f32>().ok()?.clamp(0.0, 1.0);
+            }
+        }
+
+        Some(Self { essence, quality })
+    }
+
+    /// How closely the range matches, `None` if it does not.
+    fn specificity(&self, media_type: &str) -> Option<u8> {
+        if self.essence == media_type {
+            Some(2)
+        } else if self.essence == "*/*" {
+            Some(0)
+        } else {
+            let range_type = self.essence.strip_suffix("/*")?;
+            let (media_type_type, _) = media_type.split_once('/')?;
+            (range_type == media_type_type).then_some(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn accept(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn negotiate_by_quality() {
+        assert_eq!(
+            negotiate(&HeaderMap::new(), Format::ALL),
+            Some(Format::Json)
+        );
+        assert_eq!(negotiate(&accept("*/*"), Format::ALL), Some(Format::Json));
+        assert_eq!(
+            negotiate(&accept("text/html, application/*;q=0.2"), Format::ALL),
+            Some(Format::Json)
+        );
+        assert_eq!(
+            negotiate(&accept("*/*, application/json;q=0"), &[Format::Json]),
+            None
+        );
+        assert_eq!(negotiate(&accept("text/html"), Format::ALL), None);
+        assert_eq!(negotiate(&HeaderMap::new(), &[]), None);
+    }
+
+    #[cfg(all(feature = "cbor", feature = "yaml"))]
+    #[test]
+    fn negotiate_between_formats() {
+        let formats = [Format::Json, Format::Cbor, Format::Yaml];
+
+        assert_eq!(
+            negotiate(
+                &accept("application/cbor, application/json;q=0.9"),
+                &formats
+            ),
+            Some(Format::Cbor)
+        );
+        assert_eq!(
+            negotiate(&accept("text/yaml, application/*;q=0.5"), &formats),
+            Some(Format::Yaml)
+        );
+        assert_eq!(
+            negotiate(
+                &accept("application/cbor;q=0.5, application/yaml;q=0.5"),
+                &formats
+            ),
+            Some(Format::Cbor)
+        );
+    }
+
+    #[test]
+    fn media_ranges() {
+        assert!(
+            MediaRange::parse("text/html;level=1;q=0.5").is_some_and(|range| range.quality == 0.5)
+        );
+        assert!(MediaRange::parse("text/html;q=x").is_none());
+        assert!(MediaRange::parse("html").is_none());
+
+        let range = MediaRange::parse("Application/*").unwrap();
+        assert_eq!(range.specificity("application/json"), Some(1));
+        assert_eq!(range.specificity("text/json"), None);
+    }
+}
@@ -5,17 +5,15 @@ pub trait ResultInspector<T, E> {
 
 impl<T, E> ResultInspector<T, E> for Result<T, E> {
     fn inspect(self, inspector_function: impl FnOnce(&T)) -> Self {
-        match &self {
-            Ok(value) => inspector_function(value),
-            Err(_) => (),
+        if let Ok(value) = &self {
+            inspector_function(value);
         }
         self
     }
 
     fn inspect_err(self, inspector_function: impl FnOnce(&E)) -> Self {
-        match &self {
-            Ok(_) => (),
-            Err(e) => inspector_function(e),
+        if let Err(e) = &self {
+            inspector_function(e);
         }
         self
     }
@@ -23,17 +21,15 @@ impl<T, E> ResultInspector<T, E> for Result<T, E> {
 
 impl<T, E> ResultInspector<T, E> for &Result<T, E> {
     fn inspect(self, inspector_function: impl FnOnce(&T)) -> Self {
-        match &self {
-            Ok(value) => inspector_function(value),
-            Err(_) => (),
+        if let Ok(value) = &self {
+            inspector_function(value);
         }
         self
     }
 
     fn inspect_err(self, inspector_function: impl FnOnce(&E)) -> Self {
-        match &self {
-            Ok(_) => (),
-            Err(e) => inspector_function(e),
+        if let Err(e) = &self {
+            inspector_function(e);
         }
         self
     }
@@ -46,17 +42,15 @@ pub trait OptionInspector<T> {
 
 impl<T> OptionInspector<T> for Option<T> {
     fn inspect(self, inspector_function: impl FnOnce(&T)) -> Self {
-        match &self {
-            Some(value) => inspector_function(value),
-            None => (),
+        if let Some(value) = &self {
+            inspector_function(value);
         }
         self
     }
 
     fn inspect_none(self, inspector_function: impl FnOnce()) -> Self {
-        match &self {
-            Some(_) => (),
-            None => inspector_function(),
+        if self.is_none() {
+            inspector_function();
         }
         self
     }
@@ -64,17 +58,15 @@ impl<T> OptionInspector<T> for Option<T> {
 
 impl<T> OptionInspector<T> for &Option<T> {
     fn inspect(self, inspector_function: impl FnOnce(&T)) -> Self {
-        match &self {
-            Some(value) => inspector_function(value),
-            None => (),
+        if let Some(value) = &self {
+            inspector_function(value);
         }
         self
     }
 
     fn inspect_none(self, inspector_function: impl FnOnce()) -> Self {
-        match &self {
-            Some(_) => (),
-            None => inspector_function(),
+        if self.is_none() {
+            inspector_function();
         }
         self
     }
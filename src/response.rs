@@ -1,10 +1,15 @@
-use hyper::{header::InvalidHeaderValue, http::HeaderValue};
+use hyper::{
+    header::{InvalidHeaderValue, VARY},
+    http::HeaderValue,
+    HeaderMap,
+};
 
 use crate::{
     body_utils::{
         create_bytes_body, create_json_body, create_static_str_body, create_stream_body,
         create_string_body, SerializeToJsonBodyError,
     },
+    negotiation::{negotiate, Format, NegotiationError},
     request_handler::Response,
     response_body::{AsyncStream, ResponseBody},
 };
@@ -30,6 +35,41 @@ pub fn create_json_response<T: serde::Serialize>(
     Ok(resp)
 }
 
+/// Serializes `data` in the format out of [`Format::ALL`] the `Accept` header of the request
+/// prefers, see [`negotiate`].
+pub fn create_negotiated_response<T: serde::Serialize>(
+    request_headers: &HeaderMap,
+    status: hyper::StatusCode,
+    data: &T,
+) -> Result<Response, NegotiationError> {
+    create_negotiated_response_with_formats(request_headers, Format::ALL, status, data)
+}
+
+/// Like [`create_negotiated_response`], offering only `formats`, most preferred first.
+pub fn create_negotiated_response_with_formats<T: serde::Serialize>(
+    request_headers: &HeaderMap,
+    formats: &[Format],
+    status: hyper::StatusCode,
+    data: &T,
+) -> Result<Response, NegotiationError> {
+    let format = negotiate(request_headers, formats)
+        .ok_or_else(|| NegotiationError::NotAcceptable(formats.to_vec()))?;
+
+    let mut resp = Response::default();
+    *resp.status_mut() = status;
+
+    resp.headers_mut().insert(
+        "Content-Type",
+        HeaderValue::from_static(format.media_type()),
+    );
+    resp.headers_mut()
+        .insert(VARY, HeaderValue::from_static("accept"));
+
+    *resp.body_mut() = format.serialize(data)?.into();
+
+    Ok(resp)
+}
+
 pub fn create_static_str_response(
     status: hyper::StatusCode,
     text: &'static str,
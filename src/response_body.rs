@@ -8,7 +8,9 @@ pub trait AsyncStream<ItemType>: 'static + Unpin + Send + Sync {
     ) -> Pin<Box<dyn Future<Output = Result<Option<ItemType>, Error>> + Send + Sync + 'a>>;
 }
 
+#[derive(Default)]
 pub enum ResponseBody {
+    #[default]
     None,
     Str(Option<&'static str>),
     String(Option<String>),
@@ -16,12 +18,6 @@ pub enum ResponseBody {
     AsyncBytesStream(Box<dyn AsyncStream<Vec<u8>>>),
 }
 
-impl Default for ResponseBody {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
 impl ResponseBody {
     pub async fn read_all(&mut self) -> Result<Vec<u8>, Error> {
         let mut ret = Vec::new();
@@ -31,9 +27,13 @@ impl ResponseBody {
         Ok(ret)
     }
 
+    pub async fn read_to_string(&mut self) -> Result<String, Error> {
+        Ok(String::from_utf8(self.read_all().await?)?)
+    }
+
     pub async fn read_next_chunk(&mut self) -> Result<Option<Vec<u8>>, Error> {
         match self {
-            ResponseBody::None => Ok(Some(Vec::new())),
+            ResponseBody::None => Ok(None),
             ResponseBody::Str(data) => Ok(data.take().map(|data| data.into())),
             ResponseBody::String(data) => Ok(data.take().map(|data| data.as_str().into())),
             ResponseBody::Bytes(data) => Ok(data.take()),
@@ -130,3 +130,15 @@ impl<T: AsyncStream<Vec<u8>>> From<T> for ResponseBody {
         Self::AsyncBytesStream(Box::new(value))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn empty_body_has_no_chunks() {
+        let mut body = ResponseBody::None;
+        assert!(body.read_next_chunk().await.unwrap().is_none());
+        assert!(body.read_all().await.unwrap().is_empty());
+    }
+}
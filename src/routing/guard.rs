@@ -0,0 +1,242 @@
+use hyper::header::{HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE, HOST};
+
+use crate::{extract::form_urlencoded_pairs, request_handler::Request};
+
+use super::RoutingError;
+
+/// An extra condition a request has to meet to match a route, see
+/// [`super::RouterBuilder::guard`]. Routes with the same path and method but different guards
+/// can coexist, e.g. for API versioning by media type.
+#[derive(Clone, Debug)]
+pub enum Guard {
+    Host(HostPattern),
+    /// The header has to be present, and equal to the value if there is one.
+    Header(HeaderName, Option<HeaderValue>),
+    /// The media type of the `Content-Type` header, parameters like `charset` are ignored.
+    ContentType(String),
+    /// The media type has to be listed in the `Accept` header with a non-zero quality,
+    /// wildcards like `*/*` do not match.
+    Accept(String),
+    /// The query parameter has to be present, and equal to the value if there is one.
+    Query(String, Option<String>),
+}
+
+impl Guard {
+    /// See [`HostPattern`].
+    pub fn host(pattern: &str) -> Result<Self, RoutingError> {
+        Ok(Guard::Host(HostPattern::parse(pattern)?))
+    }
+
+    pub fn header(name: HeaderName, value: HeaderValue) -> Self {
+        Guard::Header(name, Some(value))
+    }
+
+    pub fn header_present(name: HeaderName) -> Self {
+        Guard::Header(name, None)
+    }
+
+    pub fn content_type(media_type: &str) -> Self {
+        Guard::ContentType(media_type.to_ascii_lowercase())
+    }
+
+    pub fn accept(media_type: &str) -> Self {
+        Guard::Accept(media_type.to_ascii_lowercase())
+    }
+
+    pub fn query(name: &str) -> Self {
+        Guard::Query(name.to_string(), None)
+    }
+
+    pub fn query_value(name: &str, value: &str) -> Self {
+        Guard::Query(name.to_string(), Some(value.to_string()))
+    }
+
+    pub(super) fn matches(&self, req: &Request) -> bool {
+        match self {
+            Guard::Host(pattern) => request_host(req).is_some_and(|host| pattern.matches(host)),
+            Guard::Header(name, value) => {
+                let mut values = req.headers().get_all(name).iter().peekable();
+                match value {
+                    Some(value) => values.any(|header_value| header_value == value),
+                    None => values.peek().is_some(),
+                }
+            }
+            Guard::ContentType(media_type) => req
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|content_type| content_type.to_str().ok())
+                .is_some_and(|content_type| {
+                    media_type_essence(content_type).eq_ignore_ascii_case(media_type)
+                }),
+            Guard::Accept(media_type) => req
+                .headers()
+                .get_all(ACCEPT)
+                .iter()
+                .filter_map(|accept| accept.to_str().ok())
+                .flat_map(|accept| accept.split(','))
+                .any(|accepted| {
+                    media_type_essence(accepted).eq_ignore_ascii_case(media_type)
+                        && !has_zero_quality(accepted)
+                }),
+            Guard::Query(name, value) => form_urlencoded_pairs(req.uri().query().unwrap_or(""))
+                .any(|(param_name, param_value)| {
+                    &param_name == name
+                        && value
+                            .as_ref()
+                            .is_none_or(|value| value.as_str() == param_value)
+                }),
+        }
+    }
+
+    /// The parameters captured from the host of `req` by a [`Guard::Host`].
+    pub(super) fn host_params(&self, req: &Request) -> Vec<(String, String)> {
+        match self {
+            Guard::Host(pattern) => request_host(req)
+                .and_then(|host| pattern.captures(host))
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Describes the guard for the overlap detection of routes.
+    pub(super) fn key(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// A host name pattern like `api.example.com`, `*.example.com` or `{tenant}.example.com`.
+///
+/// Every label of the pattern matches exactly one label of the host, `*` matches any label and
+/// `{name}` captures it as a parameter of the route. The port is ignored and the match is case
+/// insensitive.
+#[derive(Clone, Debug)]
+pub struct HostPattern(Vec<HostLabel>);
+
+#[derive(Clone, Debug)]
+enum HostLabel {
+    Static(String),
+    Any,
+    Param(String),
+}
+
+impl HostPattern {
+    pub fn parse(pattern: &str) -> Result<Self, RoutingError> {
+        let invalid = |reason: &str| RoutingError::InvalidTemplate {
+            template: pattern.to_string(),
+            reason: reason.to_string(),
+        };
+
+        let labels = pattern
+            .split('.')
+            .map(|label| {
+                if label == "*" {
+                    Ok(HostLabel::Any)
+                } else if let Some(name) = label
+                    .strip_prefix('{')
+                    .and_then(|label| label.strip_suffix('}'))
+                {
+                    if name.is_empty()
+                        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        return Err(invalid("invalid parameter name"));
+                    }
+                    Ok(HostLabel::Param(name.to_string()))
+                } else if label.is_empty() || label.contains(['{', '}', '*', ':', '/']) {
+                    Err(invalid("invalid host label"))
+                } else {
+                    Ok(HostLabel::Static(label.to_ascii_lowercase()))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self(labels))
+    }
+
+    pub fn matches(&self, host: &str) -> bool {
+        self.captures(host).is_some()
+    }
+
+    /// The parameters of the pattern, if `host` matches it.
+    pub(super) fn captures(&self, host: &str) -> Option<Vec<(String, String)>> {
+        let host = host_without_port(host);
+        let mut labels = host.split('.');
+        let mut params = Vec::new();
+        for pattern_label in self.0.iter() {
+            let label = labels.next().filter(|label| !label.is_empty())?;
+            match pattern_label {
+                HostLabel::Static(pattern_label) => {
+                    if !label.eq_ignore_ascii_case(pattern_label) {
+                        return None;
+                    }
+                }
+                HostLabel::Any => {}
+                HostLabel::Param(name) => {
+                    params.push((name.clone(), label.to_ascii_lowercase()));
+                }
+            }
+        }
+
+        labels.next().is_none().then_some(params)
+    }
+}
+
+/// The `Host` header of HTTP/1 requests, or the authority of the URI of HTTP/2 requests.
+fn request_host(req: &Request) -> Option<&str> {
+    req.headers()
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| req.uri().host())
+}
+
+fn host_without_port(host: &str) -> &str {
+    // IPv6 literals keep their brackets, they have no labels to match anyway
+    match host.strip_prefix('[') {
+        Some(_) => host.split_inclusive(']').next().unwrap_or(host),
+        None => host.split(':').next().unwrap_or(host),
+    }
+}
+
+fn media_type_essence(media_type: &str) -> &str {
+    media_type.split(';').next().unwrap_or_default().trim()
+}
+
+fn has_zero_quality(media_range: &str) -> bool {
+    media_range.split(';').skip(1).any(|param| {
+        param
+            .trim()
+            .strip_prefix("q=")
+            .and_then(|quality| quality.trim().parse::<f32>().ok())
+            .is_some_and(|quality| quality == 0.0)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn host_patterns() {
+        let pattern = HostPattern::parse("{tenant}.example.com").unwrap();
+
+        assert_eq!(
+            pattern.captures("Acme.Example.com:8080"),
+            Some(vec![("tenant".to_string(), "acme".to_string())])
+        );
+        assert_eq!(pattern.captures("example.com"), None);
+        assert_eq!(pattern.captures("a.b.example.com"), None);
+
+        let pattern = HostPattern::parse("*.example.com").unwrap();
+        assert!(pattern.matches("www.example.com"));
+        assert!(!pattern.matches("www.example.org"));
+
+        assert!(HostPattern::parse("{}.example.com").is_err());
+        assert!(HostPattern::parse("example..com").is_err());
+    }
+
+    #[test]
+    fn accept_quality() {
+        assert!(has_zero_quality("application/json; q=0"));
+        assert!(!has_zero_quality("application/json;q=0.5"));
+        assert!(!has_zero_quality("application/json"));
+    }
+}
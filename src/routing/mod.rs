@@ -0,0 +1,1226 @@
+mod guard;
+mod normalize;
+mod openapi;
+mod template;
+mod tree;
+mod url;
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt::Display,
+    future::Future,
+    ops::Deref,
+    pin::Pin,
+    sync::{Arc, OnceLock},
+};
+
+use hyper::{
+    body::Body,
+    header::{HeaderValue, ALLOW, CONTENT_LENGTH, LOCATION},
+};
+use regex::Regex;
+
+use crate::{
+    application_context_trait::ApplicationContextTrait,
+    content_type::ContentType,
+    request_context_trait::RequestContextTrait,
+    request_handler::{
+        ErrorResponse, Request, RequestHandlerFn, RequestHandlerReturnTrait, Response,
+    },
+    response::{create_empty_response, create_string_response},
+    response_body::ResponseBody,
+};
+
+pub use guard::{Guard, HostPattern};
+pub use normalize::{PathNormalization, PathNormalizationError, TrailingSlash};
+pub use openapi::{OpenApi, ResponseMetadata, RouteMetadata};
+pub use template::{ParamType, PathParamError, PathParams, RouteTemplate};
+pub use url::{RouteUrls, UrlBuilder, UrlError};
+
+use normalize::toggle_trailing_slash;
+use openapi::OpenApiEndpoint;
+use tree::{RouteTree, TreeMatch};
+
+#[derive(Debug)]
+pub enum RoutingError {
+    Regex(regex::Error),
+    InvalidTemplate {
+        template: String,
+        reason: String,
+    },
+    /// Two routes with the same method match the same paths, the second would never be called.
+    OverlappingRoutes {
+        method: hyper::Method,
+        first: String,
+        second: String,
+    },
+    DuplicateRouteName(String),
+}
+
+impl Display for RoutingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoutingError::Regex(e) => write!(f, "{e}"),
+            RoutingError::InvalidTemplate { template, reason } => {
+                write!(f, "invalid route template '{template}': {reason}")
+            }
+            RoutingError::OverlappingRoutes {
+                method,
+                first,
+                second,
+            } => write!(
+                f,
+                "overlapping routes, {method} {second} matches the same paths as {method} {first}"
+            ),
+            RoutingError::DuplicateRouteName(name) => {
+                write!(f, "more than one route is named '{name}'")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RoutingError {}
+
+impl From<regex::Error> for RoutingError {
+    fn from(e: regex::Error) -> Self {
+        RoutingError::Regex(e)
+    }
+}
+
+/// Request extension of the routes mounted with [`RouterBuilder::nest`].
+#[derive(Clone, Debug)]
+pub struct NestedPath {
+    prefix: String,
+    remainder: String,
+}
+
+impl NestedPath {
+    fn new(prefix: &str, path: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            remainder: match &path[prefix.len()..] {
+                "" => "/".to_string(),
+                remainder => remainder.to_string(),
+            },
+        }
+    }
+
+    /// The prefix the route was mounted under, e.g. `/admin`.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// The path without the prefix, e.g. `/users/1` for `/admin/users/1`, and `/` if nothing is
+    /// left.
+    pub fn remainder(&self) -> &str {
+        &self.remainder
+    }
+}
+
+/// What the path matcher of a [`RoutingRecord`] extracted from the request path.
+#[derive(Clone)]
+enum PathMatch {
+    /// The regex of the record, prefixed when nested, and the path it matched. Regex captures
+    /// borrow the path, so they are taken when the handler is called.
+    Regex {
+        regex: Regex,
+        path: String,
+    },
+    Template(PathParams),
+}
+
+enum PathMatcher {
+    Regex(Regex),
+    Template(RouteTemplate),
+}
+
+impl PathMatcher {
+    fn with_prefix(&self, prefix: &RouteTemplate) -> Result<Self, RoutingError> {
+        Ok(match self {
+            PathMatcher::Regex(_) => {
+                let pattern = match self.pattern() {
+                    "/" => String::new(),
+                    pattern => pattern.to_string(),
+                };
+                PathMatcher::Regex(Regex::new(
+                    &("^".to_string() + &regex::escape(prefix.as_str()) + &pattern + "$"),
+                )?)
+            }
+            PathMatcher::Template(template) => PathMatcher::Template(template.with_prefix(prefix)),
+        })
+    }
+
+    /// Equal for matchers that match the same paths.
+    fn overlap_key(&self) -> String {
+        match self {
+            PathMatcher::Regex(regex) => format!("regex {}", regex.as_str()),
+            PathMatcher::Template(template) => format!("template {}", template.canonical()),
+        }
+    }
+
+    fn pattern(&self) -> &str {
+        match self {
+            PathMatcher::Regex(regex) => {
+                let pattern = regex.as_str();
+                &pattern[1..pattern.len() - 1]
+            }
+            PathMatcher::Template(template) => template.as_str(),
+        }
+    }
+}
+
+/// Regex syntax outside of character classes, paths without these are literals.
+const REGEX_META_CHARACTERS: &[char] = &[
+    '\\', '.', '+', '*', '?', '(', ')', '|', '[', ']', '{', '}', '^', '$',
+];
+
+pub type RouterFnReturnType =
+    Pin<Box<dyn Future<Output = Result<Response, ErrorResponse>> + Send + Sync>>;
+
+#[allow(type_alias_bounds)]
+type RouterFnType<
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+> = Arc<
+    dyn Fn(
+            Request,
+            Arc<ApplicationContextType>,
+            RequestContextType,
+            PathMatch,
+        ) -> RouterFnReturnType
+        + Send
+        + Sync,
+>;
+
+/// The `next` argument of a router middleware, see [`RouterBuilder::middleware`]. It
+/// implements [`RequestHandlerFn`], so the decorators in [`crate::decorators`] can be used as
+/// router middlewares.
+#[allow(type_alias_bounds)]
+pub type Next<
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+> = Box<
+    dyn Fn(Request, Arc<ApplicationContextType>, RequestContextType) -> RouterFnReturnType
+        + Send
+        + Sync,
+>;
+
+struct RoutingRecord<
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+> {
+    methods: Vec<hyper::Method>,
+    name: Option<String>,
+    /// The prefixes the route was nested under, see [`RouterBuilder::nest`].
+    prefix: Option<String>,
+    path: PathMatcher,
+    guards: Vec<Guard>,
+    metadata: RouteMetadata,
+    request_handler: RouterFnType<ApplicationContextType, RequestContextType>,
+}
+
+impl<
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+    > RoutingRecord<ApplicationContextType, RequestContextType>
+{
+    fn map_errors(&mut self, error_handler: ErrorHandlerFnType) {
+        let request_handler = self.request_handler.clone();
+        self.request_handler = Arc::new(move |req, app_context, request_context, path_match| {
+            let resp = request_handler(req, app_context, request_context, path_match);
+            let error_handler = error_handler.clone();
+            Box::pin(async move { resp.await.map_err(|e| error_handler(e)) })
+        });
+    }
+
+    fn wrap<ReturnType: RequestHandlerReturnTrait>(
+        &mut self,
+        middleware: Arc<
+            impl Fn(
+                    Next<ApplicationContextType, RequestContextType>,
+                    Request,
+                    Arc<ApplicationContextType>,
+                    RequestContextType,
+                ) -> ReturnType
+                + Send
+                + Sync
+                + 'static,
+        >,
+    ) {
+        let request_handler = self.request_handler.clone();
+        self.request_handler = Arc::new(move |req, app_context, request_context, path_match| {
+            let request_handler = request_handler.clone();
+            let next: Next<ApplicationContextType, RequestContextType> =
+                Box::new(move |req, app_context, request_context| {
+                    request_handler(req, app_context, request_context, path_match.clone())
+                });
+            Box::pin(middleware(next, req, app_context, request_context))
+        });
+    }
+}
+
+struct RoutingTable<
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+> {
+    records: Vec<RoutingRecord<ApplicationContextType, RequestContextType>>,
+    /// Template and literal routes, indices into `records`.
+    tree: RouteTree,
+    /// Regex routes, tried in the order they were added, guarded ones first, when the tree has
+    /// no match.
+    regex_routes: Vec<usize>,
+    /// Every method with a route, in the order they were first added.
+    methods: Vec<hyper::Method>,
+    fallbacks: Vec<Fallback<ApplicationContextType, RequestContextType>>,
+    error_handler: Option<ErrorHandlerFnType>,
+    normalization: PathNormalization,
+}
+
+#[allow(type_alias_bounds)]
+type FallbackFnType<
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+> = Arc<
+    dyn Fn(Request, Arc<ApplicationContextType>, RequestContextType) -> RouterFnReturnType
+        + Send
+        + Sync,
+>;
+
+type ErrorHandlerFnType = Arc<dyn Fn(ErrorResponse) -> ErrorResponse + Send + Sync>;
+
+struct Fallback<
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+> {
+    /// Empty, or the prefix the fallback was nested under.
+    prefix: String,
+    request_handler: FallbackFnType<ApplicationContextType, RequestContextType>,
+}
+
+impl<
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+    > Fallback<ApplicationContextType, RequestContextType>
+{
+    fn handles(&self, path: &str, case_insensitive: bool) -> bool {
+        let Some((prefix, rest)) = path.split_at_checked(self.prefix.len()) else {
+            return false;
+        };
+        if !rest.is_empty() && !self.prefix.is_empty() && !rest.starts_with('/') {
+            return false;
+        }
+
+        if case_insensitive {
+            prefix.eq_ignore_ascii_case(&self.prefix)
+        } else {
+            prefix == self.prefix
+        }
+    }
+
+    fn map_errors(&mut self, error_handler: ErrorHandlerFnType) {
+        let request_handler = self.request_handler.clone();
+        self.request_handler = Arc::new(move |req, app_context, request_context| {
+            let resp = request_handler(req, app_context, request_context);
+            let error_handler = error_handler.clone();
+            Box::pin(async move { resp.await.map_err(|e| error_handler(e)) })
+        });
+    }
+}
+
+pub struct RouterBuilder<
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+> {
+    routing_table: Vec<RoutingRecord<ApplicationContextType, RequestContextType>>,
+    fallbacks: Vec<Fallback<ApplicationContextType, RequestContextType>>,
+    error_handler: Option<ErrorHandlerFnType>,
+    /// The handle of this builder first, then the handles of the nested and merged builders.
+    route_urls: Vec<RouteUrls>,
+    openapi_endpoints: Vec<OpenApiEndpoint>,
+    normalization: PathNormalization,
+}
+
+pub struct Router<
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+> {
+    app_context: Arc<ApplicationContextType>,
+    routing_table: Arc<RoutingTable<ApplicationContextType, RequestContextType>>,
+    route_urls: RouteUrls,
+}
+
+impl<
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+    > Deref for Router<ApplicationContextType, RequestContextType>
+{
+    type Target = ApplicationContextType;
+
+    fn deref(&self) -> &Self::Target {
+        &self.app_context
+    }
+}
+
+impl<
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+    > Default for RouterBuilder<ApplicationContextType, RequestContextType>
+{
+    fn default() -> Self {
+        RouterBuilder::new()
+    }
+}
+
+impl<
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+    > RouterBuilder<ApplicationContextType, RequestContextType>
+{
+    pub fn new() -> Self {
+        Self {
+            routing_table: Vec::new(),
+            fallbacks: Vec::new(),
+            error_handler: None,
+            route_urls: vec![RouteUrls::default()],
+            openapi_endpoints: Vec::new(),
+            normalization: PathNormalization::default(),
+        }
+    }
+
+    /// Adds a route whose `path` is a regex matched against the whole request path.
+    ///
+    /// Paths without regex syntax, e.g. `/users/me`, are matched by the route tree like the
+    /// routes added with [`RouterBuilder::route`]. Real regexes are only tried, in the order
+    /// they were added, after the route tree found no route for the request.
+    pub fn path<
+        ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+    >(
+        mut self,
+        methods: &[hyper::Method],
+        path: impl ToString,
+        request_handler: impl RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+    ) -> Result<Self, regex::Error> {
+        let path = path.to_string();
+        let path =
+            match RouteTemplate::literal(&path).filter(|_| !path.contains(REGEX_META_CHARACTERS)) {
+                Some(template) => PathMatcher::Template(template),
+                None => PathMatcher::Regex(Regex::new(&("^".to_string() + &path + "$"))?),
+            };
+        self.routing_table.push(RoutingRecord {
+            methods: methods.into(),
+            name: None,
+            prefix: None,
+            guards: Vec::new(),
+            metadata: RouteMetadata::default(),
+            path,
+            request_handler: Arc::new(move |req, app_context, request_context, _path_match| {
+                Box::pin(request_handler(req, app_context, request_context))
+            }),
+        });
+
+        Ok(self)
+    }
+
+    pub fn path_with_params<
+        ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+    >(
+        mut self,
+        methods: &[hyper::Method],
+        path: impl ToString,
+        request_handler: impl Fn(
+                Request,
+                Arc<ApplicationContextType>,
+                RequestContextType,
+                regex::Captures,
+            ) -> ReturnType
+            + Send
+            + Sync
+            + 'static,
+    ) -> Result<Self, regex::Error> {
+        let path = Regex::new(&("^".to_string() + &path.to_string() + "$"))?;
+        self.routing_table.push(RoutingRecord {
+            methods: methods.into(),
+            name: None,
+            prefix: None,
+            guards: Vec::new(),
+            metadata: RouteMetadata::default(),
+            path: PathMatcher::Regex(path),
+            request_handler: Arc::new(move |req, app_context, request_context, path_match| {
+                let PathMatch::Regex { regex, path } = path_match else {
+                    unreachable!("regex routes are matched with regex captures")
+                };
+                match regex.captures(&path) {
+                    Some(captures) => {
+                        Box::pin(request_handler(req, app_context, request_context, captures))
+                    }
+                    None => Box::pin(std::future::ready(Err(create_empty_response(
+                        hyper::StatusCode::NOT_FOUND,
+                    )
+                    .into()))),
+                }
+            }),
+        });
+
+        Ok(self)
+    }
+
+    /// Adds a route with named, typed path parameters, see [`RouteTemplate`], e.g.
+    /// `/users/{user_id:u64}/posts/{slug}`.
+    ///
+    /// Routes are matched segment by segment, static segments take priority over parameters
+    /// and parameters over wildcards, independently of the order the routes were added in.
+    /// Requests whose path has the shape of the template but a parameter of the wrong type are
+    /// answered with `400 Bad Request`, unless another route matches them.
+    pub fn route<
+        ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+    >(
+        mut self,
+        methods: &[hyper::Method],
+        template: &str,
+        request_handler: impl Fn(Request, Arc<ApplicationContextType>, RequestContextType, PathParams) -> ReturnType
+            + Send
+            + Sync
+            + 'static,
+    ) -> Result<Self, RoutingError> {
+        self.routing_table.push(RoutingRecord {
+            methods: methods.into(),
+            name: None,
+            prefix: None,
+            guards: Vec::new(),
+            metadata: RouteMetadata::default(),
+            path: PathMatcher::Template(RouteTemplate::parse(template)?),
+            request_handler: Arc::new(move |req, app_context, request_context, path_match| {
+                let PathMatch::Template(params) = path_match else {
+                    unreachable!("template routes are matched with path params")
+                };
+                Box::pin(request_handler(req, app_context, request_context, params))
+            }),
+        });
+
+        Ok(self)
+    }
+
+    /// Wraps every route added so far with `middleware`, e.g. a decorator like
+    /// [`crate::decorators::access_token_handler`].
+    ///
+    /// Build the routes sharing middlewares in a separate builder and [`RouterBuilder::merge`]
+    /// or [`RouterBuilder::nest`] it, to keep them apart from the other routes. Middlewares
+    /// added later run first. They run only for requests matching a route, not for the
+    /// automatic `404`, `405` and `OPTIONS` answers.
+    pub fn middleware<ReturnType: RequestHandlerReturnTrait>(
+        mut self,
+        middleware: impl Fn(
+                Next<ApplicationContextType, RequestContextType>,
+                Request,
+                Arc<ApplicationContextType>,
+                RequestContextType,
+            ) -> ReturnType
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        let middleware = Arc::new(middleware);
+        for record in self.routing_table.iter_mut() {
+            record.wrap(middleware.clone());
+        }
+        self
+    }
+
+    /// Wraps the route added last with `middleware`, see [`RouterBuilder::middleware`].
+    pub fn route_middleware<ReturnType: RequestHandlerReturnTrait>(
+        mut self,
+        middleware: impl Fn(
+                Next<ApplicationContextType, RequestContextType>,
+                Request,
+                Arc<ApplicationContextType>,
+                RequestContextType,
+            ) -> ReturnType
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        if let Some(record) = self.routing_table.last_mut() {
+            record.wrap(Arc::new(middleware));
+        }
+        self
+    }
+
+    /// Adds a condition to the route added last, it only matches requests passing all of its
+    /// guards. The captures of a [`Guard::Host`] pattern are added to the [`PathParams`] of
+    /// template routes.
+    ///
+    /// Routes with more guards are tried first, so a guarded route can narrow down a route
+    /// with the same path, e.g. to serve another version of a resource for an `Accept` media
+    /// type. A request failing the guards of every route of its path is handled as if the path
+    /// had no routes for its method.
+    pub fn guard(mut self, guard: Guard) -> Self {
+        if let Some(record) = self.routing_table.last_mut() {
+            record.guards.push(guard);
+        }
+        self
+    }
+
+    /// Mounts the routes of `router_builder` under the literal `prefix`, e.g. `/admin`.
+    ///
+    /// The nested request handlers find the prefix and the rest of the path in the
+    /// [`NestedPath`] request extension.
+    pub fn nest(
+        mut self,
+        prefix: &str,
+        router_builder: RouterBuilder<ApplicationContextType, RequestContextType>,
+    ) -> Result<Self, RoutingError> {
+        let prefix_template = RouteTemplate::literal(prefix)
+            .filter(|_| !prefix.ends_with('/') && !prefix.contains(REGEX_META_CHARACTERS))
+            .ok_or_else(|| RoutingError::InvalidTemplate {
+                template: prefix.to_string(),
+                reason: "a prefix has to be a literal path without a trailing '/'".into(),
+            })?;
+
+        let (routing_table, fallbacks, route_urls, openapi_endpoints) = router_builder.into_parts();
+        self.route_urls.extend(route_urls);
+        self.openapi_endpoints.extend(openapi_endpoints);
+        for record in routing_table {
+            self.routing_table.push(RoutingRecord {
+                path: record.path.with_prefix(&prefix_template)?,
+                prefix: Some(prefix.to_string() + record.prefix.as_deref().unwrap_or_default()),
+                ..record
+            });
+        }
+        for fallback in fallbacks {
+            self.fallbacks.push(Fallback {
+                prefix: prefix.to_string() + &fallback.prefix,
+                ..fallback
+            });
+        }
+
+        Ok(self)
+    }
+
+    /// Adds the routes of `router_builder` as they are.
+    ///
+    /// The fallback of `router_builder` is only used if this builder has none.
+    pub fn merge(
+        mut self,
+        router_builder: RouterBuilder<ApplicationContextType, RequestContextType>,
+    ) -> Self {
+        let (routing_table, fallbacks, route_urls, openapi_endpoints) = router_builder.into_parts();
+        self.route_urls.extend(route_urls);
+        self.openapi_endpoints.extend(openapi_endpoints);
+        self.routing_table.extend(routing_table);
+        self.fallbacks.extend(fallbacks);
+        self
+    }
+
+    /// Sets the request handler called instead of answering `404 Not Found`, e.g. to serve the
+    /// `index.html` of a single page application or to send a JSON body.
+    ///
+    /// The fallback of a builder mounted with [`RouterBuilder::nest`] is only called for the
+    /// paths under its prefix, and it finds the prefix in the [`NestedPath`] request extension.
+    /// If several fallbacks match, the one with the longest prefix is called.
+    pub fn fallback<ReturnType: RequestHandlerReturnTrait>(
+        mut self,
+        request_handler: impl RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+    ) -> Self {
+        self.fallbacks
+            .retain(|fallback| !fallback.prefix.is_empty());
+        self.fallbacks.insert(
+            0,
+            Fallback {
+                prefix: String::new(),
+                request_handler: Arc::new(move |req, app_context, request_context| {
+                    Box::pin(request_handler(req, app_context, request_context))
+                }),
+            },
+        );
+        self
+    }
+
+    /// Sets the function every [`ErrorResponse`] of the router passes through, e.g. to wrap
+    /// them in a standard error envelope. It sees the errors of the request handlers, the
+    /// fallback and the middlewares, and the `400`, `404` and `405` answers of the router.
+    ///
+    /// The errors of the routes of a builder mounted with [`RouterBuilder::nest`] or
+    /// [`RouterBuilder::merge`] pass through the error handler of that builder first.
+    pub fn error_handler(
+        mut self,
+        error_handler: impl Fn(ErrorResponse) -> ErrorResponse + Send + Sync + 'static,
+    ) -> Self {
+        self.error_handler = Some(Arc::new(error_handler));
+        self
+    }
+
+    /// Names the route added last, so its URL can be generated with [`Router::url_for`] or
+    /// [`RouteUrls::url_for`]. Names have to be unique in the whole router, nested and merged
+    /// builders included.
+    pub fn name(mut self, name: &str) -> Self {
+        if let Some(record) = self.routing_table.last_mut() {
+            record.name = Some(name.to_string());
+        }
+        self
+    }
+
+    /// Generates the URLs of the named routes once the router is built, e.g. in the
+    /// application context passed to [`RouterBuilder::build`]. If this builder gets nested,
+    /// the URLs include the prefix.
+    pub fn urls(&self) -> RouteUrls {
+        self.route_urls[0].clone()
+    }
+
+    /// Sets how request paths are normalized before matching, see [`PathNormalization`]. Only
+    /// the normalization of the builder that is built is used, nested and merged builders
+    /// follow it.
+    pub fn normalization(mut self, normalization: PathNormalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Attaches documentation to the route added last, see [`Router::routes`] and [`OpenApi`].
+    pub fn metadata(mut self, metadata: RouteMetadata) -> Self {
+        if let Some(record) = self.routing_table.last_mut() {
+            record.metadata = metadata;
+        }
+        self
+    }
+
+    /// Adds a `GET` route at `path`, taken literally, serving the OpenAPI document of the whole
+    /// router, generated when the router is built. The route itself is left out of the
+    /// document.
+    pub fn openapi(mut self, path: &str, openapi: OpenApi) -> Result<Self, RoutingError> {
+        let template =
+            RouteTemplate::literal(path).ok_or_else(|| RoutingError::InvalidTemplate {
+                template: path.to_string(),
+                reason: "a path has to start with a '/'".into(),
+            })?;
+
+        let document = Arc::new(OnceLock::new());
+        self.openapi_endpoints.push(OpenApiEndpoint {
+            openapi,
+            document: document.clone(),
+        });
+        self.routing_table.push(RoutingRecord {
+            methods: vec![hyper::Method::GET],
+            name: None,
+            prefix: None,
+            guards: Vec::new(),
+            metadata: RouteMetadata::new().hidden(),
+            path: PathMatcher::Template(template),
+            request_handler: Arc::new(move |_req, _app_context, _request_context, _path_match| {
+                let resp = match document.get() {
+                    Some(document) => Ok(create_string_response(
+                        hyper::StatusCode::OK,
+                        document,
+                        ContentType::ApplicationJson,
+                    )),
+                    None => Err(create_empty_response(hyper::StatusCode::NOT_FOUND).into()),
+                };
+                Box::pin(std::future::ready(resp))
+            }),
+        });
+
+        Ok(self)
+    }
+
+    /// The routes and fallbacks with the error handler applied, for nesting and merging.
+    #[allow(clippy::type_complexity)]
+    fn into_parts(
+        self,
+    ) -> (
+        Vec<RoutingRecord<ApplicationContextType, RequestContextType>>,
+        Vec<Fallback<ApplicationContextType, RequestContextType>>,
+        Vec<RouteUrls>,
+        Vec<OpenApiEndpoint>,
+    ) {
+        let mut routing_table = self.routing_table;
+        let mut fallbacks = self.fallbacks;
+        if let Some(error_handler) = self.error_handler {
+            for record in routing_table.iter_mut() {
+                record.map_errors(error_handler.clone());
+            }
+            for fallback in fallbacks.iter_mut() {
+                fallback.map_errors(error_handler.clone());
+            }
+        }
+
+        (
+            routing_table,
+            fallbacks,
+            self.route_urls,
+            self.openapi_endpoints,
+        )
+    }
+
+    /// Overlapping routes and duplicate route names are logged, the route added first is
+    /// called and keeps the name, see [`RouterBuilder::try_build`] to reject them instead.
+    pub fn build(
+        self,
+        app_context: ApplicationContextType,
+    ) -> Router<ApplicationContextType, RequestContextType> {
+        match self.build_router(app_context, false) {
+            Ok(router) => router,
+            Err(_) => unreachable!("lenient router builds do not fail"),
+        }
+    }
+
+    /// Fails with [`RoutingError::OverlappingRoutes`] if two routes with a common method and the
+    /// same guards match the same paths, e.g. `/users/{id}` and `/users/{name}`, and with
+    /// [`RoutingError::DuplicateRouteName`] if two routes have the same name.
+    pub fn try_build(
+        self,
+        app_context: ApplicationContextType,
+    ) -> Result<Router<ApplicationContextType, RequestContextType>, RoutingError> {
+        self.build_router(app_context, true)
+    }
+
+    fn build_router(
+        self,
+        app_context: ApplicationContextType,
+        strict: bool,
+    ) -> Result<Router<ApplicationContextType, RequestContextType>, RoutingError> {
+        let check = |e: RoutingError| {
+            if strict {
+                return Err(e);
+            }
+            log::warn!("{e}, the route added first is used");
+            Ok(())
+        };
+
+        let mut named_routes = HashMap::new();
+        for record in self.routing_table.iter() {
+            if let Some(name) = &record.name {
+                let template = match &record.path {
+                    PathMatcher::Template(template) => Some(template.clone()),
+                    PathMatcher::Regex(_) => None,
+                };
+                if named_routes.contains_key(name) {
+                    check(RoutingError::DuplicateRouteName(name.clone()))?;
+                } else {
+                    named_routes.insert(name.clone(), template);
+                }
+            }
+        }
+
+        let mut route_patterns: HashMap<_, &str> = HashMap::new();
+        for record in self.routing_table.iter() {
+            let mut guard_keys: Vec<String> = record.guards.iter().map(Guard::key).collect();
+            guard_keys.sort();
+            let path_key = match &record.path {
+                PathMatcher::Template(_) if self.normalization.is_case_insensitive() => {
+                    record.path.overlap_key().to_ascii_lowercase()
+                }
+                _ => record.path.overlap_key(),
+            };
+            let overlap_key = (path_key, guard_keys);
+            for method in record.methods.iter() {
+                match route_patterns.entry((method.clone(), overlap_key.clone())) {
+                    Entry::Occupied(first) => check(RoutingError::OverlappingRoutes {
+                        method: method.clone(),
+                        first: first.get().to_string(),
+                        second: record.path.pattern().to_string(),
+                    })?,
+                    Entry::Vacant(entry) => {
+                        entry.insert(record.path.pattern());
+                    }
+                }
+            }
+        }
+
+        let mut methods: Vec<hyper::Method> = Vec::new();
+        for record in self.routing_table.iter() {
+            for method in record.methods.iter() {
+                if !methods.contains(method) {
+                    methods.push(method.clone());
+                }
+            }
+        }
+
+        // routes with guards are tried before the routes they narrow down
+        let mut route_order: Vec<usize> = (0..self.routing_table.len()).collect();
+        route_order.sort_by_key(|index| std::cmp::Reverse(self.routing_table[*index].guards.len()));
+
+        let mut tree = RouteTree::new(self.normalization.is_case_insensitive());
+        let mut regex_routes = Vec::new();
+        for index in route_order {
+            match &self.routing_table[index].path {
+                PathMatcher::Template(template) => tree.insert(template, index),
+                PathMatcher::Regex(_) => regex_routes.push(index),
+            }
+        }
+
+        let named_routes = Arc::new(named_routes);
+        for route_urls in self.route_urls.iter() {
+            route_urls.set(named_routes.clone());
+        }
+
+        let router = Router {
+            app_context: Arc::new(app_context),
+            routing_table: Arc::new(RoutingTable {
+                records: self.routing_table,
+                tree,
+                regex_routes,
+                methods,
+                fallbacks: self.fallbacks,
+                error_handler: self.error_handler,
+                normalization: self.normalization,
+            }),
+            route_urls: self.route_urls[0].clone(),
+        };
+
+        for endpoint in self.openapi_endpoints.iter() {
+            let _ = endpoint
+                .document
+                .set(endpoint.openapi.document(&router).to_string());
+        }
+
+        Ok(router)
+    }
+}
+
+impl<
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+    > Router<ApplicationContextType, RequestContextType>
+{
+    /// Calls the handler of the route matching the request.
+    ///
+    /// `HEAD` requests without a `HEAD` route are answered by the `GET` route without the
+    /// body, and `OPTIONS` requests without an `OPTIONS` route with the methods the path has
+    /// routes for. If the path has routes, but not for the method of the request, the answer
+    /// is `405 Method Not Allowed` with an `Allow` header.
+    pub async fn dispatch(
+        &self,
+        mut req: Request,
+        app_context: Arc<ApplicationContextType>,
+        request_context: RequestContextType,
+    ) -> Result<Response, ErrorResponse> {
+        let normalization = &self.routing_table.normalization;
+        let mut path = String::new();
+        let mut strip_body = false;
+        let lookup = match normalization.normalize(req.uri().path()) {
+            Ok(normalized_path) => {
+                path = normalized_path;
+                let mut lookup;
+                (lookup, strip_body) = self.lookup_request(&req, &path);
+
+                if !matches!(lookup, RouteLookup::Found(..))
+                    && normalization.trailing_slash_policy() != TrailingSlash::Strict
+                {
+                    if let Some(alternate_path) = toggle_trailing_slash(&path) {
+                        if let (alternate_lookup @ RouteLookup::Found(..), alternate_strip_body) =
+                            self.lookup_request(&req, &alternate_path)
+                        {
+                            if normalization.trailing_slash_policy() == TrailingSlash::Redirect {
+                                lookup = normalization
+                                    .redirect_location(&alternate_path)
+                                    .map_or(RouteLookup::NotFound, RouteLookup::Redirect);
+                            } else {
+                                lookup = alternate_lookup;
+                                strip_body = alternate_strip_body;
+                                path = alternate_path;
+                            }
+                        }
+                    }
+                }
+
+                lookup
+            }
+            Err(e) => RouteLookup::InvalidPath(e),
+        };
+
+        let resp = match lookup {
+            RouteLookup::Found(route, path_match) => {
+                let record = &self.routing_table.records[route];
+                if let Some(prefix) = &record.prefix {
+                    req.extensions_mut().insert(NestedPath::new(prefix, &path));
+                }
+
+                record.request_handler.as_ref()(req, app_context, request_context, path_match).await
+            }
+            RouteLookup::InvalidParam(e) => {
+                self.no_route_response(req, &path, Some(e), app_context, request_context)
+                    .await
+            }
+            RouteLookup::NotFound => {
+                self.no_route_response(req, &path, None, app_context, request_context)
+                    .await
+            }
+            RouteLookup::Redirect(mut location) => {
+                if let Some(query) = req.uri().query() {
+                    location = location + "?" + query;
+                }
+                let mut resp = create_empty_response(hyper::StatusCode::PERMANENT_REDIRECT);
+                match HeaderValue::from_str(&location) {
+                    Ok(location) => {
+                        resp.headers_mut().insert(LOCATION, location);
+                        Ok(resp)
+                    }
+                    Err(_) => Err(create_empty_response(hyper::StatusCode::BAD_REQUEST).into()),
+                }
+            }
+            RouteLookup::InvalidPath(e) => Err(e.into()),
+        };
+
+        let resp = match &self.routing_table.error_handler {
+            Some(error_handler) => resp.map_err(|e| error_handler(e)),
+            None => resp,
+        };
+
+        if strip_body {
+            resp.map(strip_response_body)
+                .map_err(|e| ErrorResponse(strip_response_body(e.0)))
+        } else {
+            resp
+        }
+    }
+
+    /// Generates the URL of a route named with [`RouterBuilder::name`], e.g.
+    /// `router.url_for("user").param("id", 42).query("tab", "posts").build()?`.
+    pub fn url_for(&self, name: &str) -> UrlBuilder<'_> {
+        self.route_urls.url_for(name)
+    }
+
+    pub fn urls(&self) -> &RouteUrls {
+        &self.route_urls
+    }
+
+    /// The routes of the router, in the order they were added.
+    pub fn routes(&self) -> impl Iterator<Item = RouteInfo<'_>> {
+        self.routing_table.records.iter().map(|record| RouteInfo {
+            methods: &record.methods,
+            pattern: record.path.pattern(),
+            template: match &record.path {
+                PathMatcher::Template(template) => Some(template),
+                PathMatcher::Regex(_) => None,
+            },
+            name: record.name.as_deref(),
+            guards: &record.guards,
+            metadata: &record.metadata,
+        })
+    }
+
+    /// Returns the pattern of the route that handles a request with `method` and `path`, e.g.
+    /// to label metrics by route instead of by path. The path is normalized, but guards are not
+    /// checked.
+    pub fn find_route(&self, method: &hyper::Method, path: &str) -> Option<&str> {
+        let path = self.routing_table.normalization.normalize(path).ok()?;
+        match self.lookup(method, &path, None) {
+            RouteLookup::Found(route, _) => Some(self.routing_table.records[route].path.pattern()),
+            _ => None,
+        }
+    }
+
+    /// The methods `path` has routes for, including the automatic `HEAD` and `OPTIONS`, or
+    /// nothing if it has no routes at all. Guards are not checked.
+    pub fn allowed_methods(&self, path: &str) -> Vec<hyper::Method> {
+        self.allowed_methods_for(path, None)
+    }
+
+    /// The methods of the routes of `path` whose guards `req` passes, if there is a request.
+    fn allowed_methods_for(&self, path: &str, req: Option<&Request>) -> Vec<hyper::Method> {
+        let mut allowed_methods: Vec<hyper::Method> = self
+            .routing_table
+            .methods
+            .iter()
+            .filter(|method| matches!(self.lookup(method, path, req), RouteLookup::Found(..)))
+            .cloned()
+            .collect();
+
+        if allowed_methods.is_empty() {
+            return allowed_methods;
+        }
+        if allowed_methods.contains(&hyper::Method::GET)
+            && !allowed_methods.contains(&hyper::Method::HEAD)
+        {
+            allowed_methods.push(hyper::Method::HEAD);
+        }
+        if !allowed_methods.contains(&hyper::Method::OPTIONS) {
+            allowed_methods.push(hyper::Method::OPTIONS);
+        }
+
+        allowed_methods
+    }
+
+    async fn no_route_response(
+        &self,
+        mut req: Request,
+        path: &str,
+        invalid_param: Option<PathParamError>,
+        app_context: Arc<ApplicationContextType>,
+        request_context: RequestContextType,
+    ) -> Result<Response, ErrorResponse> {
+        let allowed_methods = self.allowed_methods_for(path, Some(&req));
+        if allowed_methods.is_empty() {
+            if let Some(e) = invalid_param {
+                return Err(e.into());
+            }
+
+            let fallback = self
+                .routing_table
+                .fallbacks
+                .iter()
+                .rev()
+                .filter(|fallback| {
+                    fallback.handles(path, self.routing_table.normalization.is_case_insensitive())
+                })
+                .max_by_key(|fallback| fallback.prefix.len());
+            return match fallback {
+                Some(fallback) => {
+                    if !fallback.prefix.is_empty() {
+                        req.extensions_mut()
+                            .insert(NestedPath::new(&fallback.prefix, path));
+                    }
+                    (fallback.request_handler)(req, app_context, request_context).await
+                }
+                None => Err(create_empty_response(hyper::StatusCode::NOT_FOUND).into()),
+            };
+        }
+
+        let allow = allowed_methods
+            .iter()
+            .map(hyper::Method::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let allow = HeaderValue::from_str(&allow).ok();
+
+        if req.method() == hyper::Method::OPTIONS {
+            let mut resp = create_empty_response(hyper::StatusCode::NO_CONTENT);
+            if let Some(allow) = allow {
+                resp.headers_mut().insert(ALLOW, allow);
+            }
+            Ok(resp)
+        } else {
+            let mut resp = create_empty_response(hyper::StatusCode::METHOD_NOT_ALLOWED);
+            if let Some(allow) = allow {
+                resp.headers_mut().insert(ALLOW, allow);
+            }
+            Err(resp.into())
+        }
+    }
+
+    /// Finds the route of `req` for the normalized `path`, and whether the route is the `GET`
+    /// route answering a `HEAD` request.
+    fn lookup_request(&self, req: &Request, path: &str) -> (RouteLookup, bool) {
+        let lookup = self.lookup(req.method(), path, Some(req));
+        if req.method() == hyper::Method::HEAD && !matches!(lookup, RouteLookup::Found(..)) {
+            if let get_lookup @ RouteLookup::Found(..) =
+                self.lookup(&hyper::Method::GET, path, Some(req))
+            {
+                return (get_lookup, true);
+            }
+        }
+
+        (lookup, false)
+    }
+
+    /// Finds the route of `method` and `path`, checking the guards of the routes if there is
+    /// a request.
+    fn lookup(&self, method: &hyper::Method, path: &str, req: Option<&Request>) -> RouteLookup {
+        let records = &self.routing_table.records;
+        let accepts = |route: usize| {
+            let record = &records[route];
+            record.methods.contains(method)
+                && req.is_none_or(|req| record.guards.iter().all(|guard| guard.matches(req)))
+        };
+
+        let invalid_param = match self.routing_table.tree.find(path, accepts) {
+            TreeMatch::Matched(route, mut params) => {
+                if let Some(req) = req {
+                    for guard in records[route].guards.iter() {
+                        params.0.extend(guard.host_params(req));
+                    }
+                }
+                return RouteLookup::Found(route, PathMatch::Template(params));
+            }
+            TreeMatch::InvalidParam(e) => Some(e),
+            TreeMatch::NoMatch => None,
+        };
+
+        let regex_route = self
+            .routing_table
+            .regex_routes
+            .iter()
+            .copied()
+            .filter(|route| accepts(*route))
+            .find_map(|route| match &records[route].path {
+                PathMatcher::Regex(regex) if regex.is_match(path) => Some((route, regex)),
+                _ => None,
+            });
+
+        match (regex_route, invalid_param) {
+            (Some((route, regex)), _) => RouteLookup::Found(
+                route,
+                PathMatch::Regex {
+                    regex: regex.clone(),
+                    path: path.to_string(),
+                },
+            ),
+            (None, Some(e)) => RouteLookup::InvalidParam(e),
+            (None, None) => RouteLookup::NotFound,
+        }
+    }
+}
+
+/// A route of a [`Router`], see [`Router::routes`].
+pub struct RouteInfo<'a> {
+    pub methods: &'a [hyper::Method],
+    /// The template, or the regex without the anchors.
+    pub pattern: &'a str,
+    /// `None` for regex routes.
+    pub template: Option<&'a RouteTemplate>,
+    pub name: Option<&'a str>,
+    pub guards: &'a [Guard],
+    pub metadata: &'a RouteMetadata,
+}
+
+/// Removes the body of the response to a `HEAD` request, keeping its `Content-Length`.
+fn strip_response_body(mut resp: Response) -> Response {
+    if !resp.headers().contains_key(CONTENT_LENGTH) {
+        if let Some(content_length) = resp.body().size_hint().exact() {
+            resp.headers_mut()
+                .insert(CONTENT_LENGTH, HeaderValue::from(content_length));
+        }
+    }
+    *resp.body_mut() = ResponseBody::None;
+
+    resp
+}
+
+enum RouteLookup {
+    Found(usize, PathMatch),
+    InvalidParam(PathParamError),
+    NotFound,
+    /// The path with the trailing slash toggled has a route, see [`TrailingSlash::Redirect`].
+    Redirect(String),
+    InvalidPath(PathNormalizationError),
+}
+
+impl<
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+    > ApplicationContextTrait for Router<ApplicationContextType, RequestContextType>
+{
+}
+
+impl<
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+    > RequestContextTrait<Router<ApplicationContextType, RequestContextType>>
+    for RequestContextType
+{
+    fn create(app_context: Arc<Router<ApplicationContextType, RequestContextType>>) -> Self {
+        Self::create(app_context.app_context.clone())
+    }
+}
+
+pub async fn router_fn<
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+>(
+    req: Request,
+    router: Arc<Router<ApplicationContextType, RequestContextType>>,
+    request_context: RequestContextType,
+) -> Result<Response, ErrorResponse> {
+    router
+        .as_ref()
+        .dispatch(req, router.app_context.clone(), request_context)
+        .await
+}
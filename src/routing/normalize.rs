@@ -0,0 +1,231 @@
+use std::fmt::Display;
+
+use percent_encoding::{AsciiSet, CONTROLS};
+
+use crate::{
+    content_type::ContentType, request_handler::ErrorResponse, response::create_string_response,
+};
+
+/// What the router does with a path that only matches a route with the trailing slash added
+/// or removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// The path does not match.
+    #[default]
+    Strict,
+    /// The route is called as if the path matched.
+    Match,
+    /// The request is answered with `308 Permanent Redirect` to the path of the route.
+    Redirect,
+}
+
+/// The characters encoded in the location of a trailing slash redirect, the path
+/// percent-encode set of the URL standard.
+const PATH_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// How the request path is normalized before it is matched against the routes, see
+/// [`super::RouterBuilder::normalization`]. Nothing is normalized by default.
+///
+/// Request handlers find the original path in the URI of the request, while the
+/// [`super::PathParams`] and the [`super::NestedPath`] are taken from the normalized path.
+#[derive(Clone, Debug, Default)]
+pub struct PathNormalization {
+    trailing_slash: TrailingSlash,
+    collapse_slashes: bool,
+    decode_percent: bool,
+    case_insensitive: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathNormalizationError {
+    /// A percent-encoded segment is not valid UTF-8.
+    InvalidEncoding(String),
+    /// A segment contains an encoded `/`, which would change the segments of the path.
+    EncodedSlash(String),
+    /// A `..` segment, written out or encoded.
+    Traversal,
+}
+
+impl Display for PathNormalizationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathNormalizationError::InvalidEncoding(segment) => {
+                write!(f, "path segment '{segment}' is not valid UTF-8")
+            }
+            PathNormalizationError::EncodedSlash(segment) => {
+                write!(f, "path segment '{segment}' contains an encoded '/'")
+            }
+            PathNormalizationError::Traversal => write!(f, "path contains a '..' segment"),
+        }
+    }
+}
+
+impl std::error::Error for PathNormalizationError {}
+
+impl From<PathNormalizationError> for ErrorResponse {
+    fn from(e: PathNormalizationError) -> Self {
+        create_string_response(hyper::StatusCode::BAD_REQUEST, e, ContentType::TextPlain).into()
+    }
+}
+
+impl PathNormalization {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn trailing_slash(mut self, trailing_slash: TrailingSlash) -> Self {
+        self.trailing_slash = trailing_slash;
+        self
+    }
+
+    /// Matches `/users//42` like `/users/42`.
+    pub fn collapse_slashes(mut self, collapse_slashes: bool) -> Self {
+        self.collapse_slashes = collapse_slashes;
+        self
+    }
+
+    /// Decodes percent-encoded segments before matching, e.g. `/h%65llo` matches `/hello`.
+    /// Paths with an encoded `/` or with a `..` segment are answered with `400 Bad Request`.
+    pub fn decode_percent(mut self, decode_percent: bool) -> Self {
+        self.decode_percent = decode_percent;
+        self
+    }
+
+    /// Matches the static segments of template routes regardless of case, e.g. `/Hello`
+    /// matches `/hello`. Regex routes and the values of parameters are not affected.
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    pub(super) fn trailing_slash_policy(&self) -> TrailingSlash {
+        self.trailing_slash
+    }
+
+    pub(super) fn is_case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    /// The location of a redirect to the normalized `path`, re-encoded if it was decoded.
+    /// `None` for paths starting with `//`, which clients would take for a different host.
+    pub(super) fn redirect_location(&self, path: &str) -> Option<String> {
+        if path.starts_with("//") {
+            return None;
+        }
+
+        Some(if self.decode_percent {
+            percent_encoding::utf8_percent_encode(path, PATH_ENCODE_SET).to_string()
+        } else {
+            path.to_string()
+        })
+    }
+
+    pub(super) fn normalize(&self, path: &str) -> Result<String, PathNormalizationError> {
+        let segments = match path.strip_prefix('/') {
+            Some(path) if self.collapse_slashes || self.decode_percent => path.split('/'),
+            _ => return Ok(path.to_string()),
+        };
+
+        let mut normalized = String::with_capacity(path.len());
+        let segment_count = segments.clone().count();
+        for (index, segment) in segments.enumerate() {
+            // the empty segment after a trailing slash is kept
+            if self.collapse_slashes && segment.is_empty() && index + 1 < segment_count {
+                continue;
+            }
+
+            normalized.push('/');
+            if self.decode_percent {
+                if segment == ".." {
+                    return Err(PathNormalizationError::Traversal);
+                }
+
+                let decoded = percent_encoding::percent_decode_str(segment)
+                    .decode_utf8()
+                    .map_err(|_| PathNormalizationError::InvalidEncoding(segment.to_string()))?;
+                if decoded.contains('/') {
+                    return Err(PathNormalizationError::EncodedSlash(segment.to_string()));
+                }
+                if decoded == ".." {
+                    return Err(PathNormalizationError::Traversal);
+                }
+                normalized.push_str(&decoded);
+            } else {
+                normalized.push_str(segment);
+            }
+        }
+
+        Ok(normalized)
+    }
+}
+
+/// `path` with the trailing slash removed, or added if it has none.
+pub(super) fn toggle_trailing_slash(path: &str) -> Option<String> {
+    match path.strip_suffix('/') {
+        Some("") => None,
+        Some(path) => Some(path.to_string()),
+        None => Some(path.to_string() + "/"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn collapses_and_decodes() {
+        let normalization = PathNormalization::new()
+            .collapse_slashes(true)
+            .decode_percent(true);
+
+        for (path, normalized) in [
+            ("/", "/"),
+            ("//", "/"),
+            ("/h%65llo//world/", "/hello/world/"),
+            ("/a///b", "/a/b"),
+            ("/caf%C3%A9", "/café"),
+        ] {
+            assert_eq!(normalization.normalize(path).unwrap(), normalized, "{path}");
+        }
+
+        assert_eq!(
+            normalization.normalize("/a%2Fb"),
+            Err(PathNormalizationError::EncodedSlash("a%2Fb".into()))
+        );
+        for path in ["/a/../b", "/a/%2e%2E/b"] {
+            assert_eq!(
+                normalization.normalize(path),
+                Err(PathNormalizationError::Traversal),
+                "{path}"
+            );
+        }
+        assert!(matches!(
+            normalization.normalize("/%FF"),
+            Err(PathNormalizationError::InvalidEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn keeps_the_path_by_default() {
+        let normalization = PathNormalization::new();
+
+        assert_eq!(normalization.normalize("/a//%2F/").unwrap(), "/a//%2F/");
+    }
+
+    #[test]
+    fn toggles_trailing_slash() {
+        assert_eq!(toggle_trailing_slash("/"), None);
+        assert_eq!(toggle_trailing_slash("/hello/").as_deref(), Some("/hello"));
+        assert_eq!(toggle_trailing_slash("/hello").as_deref(), Some("/hello/"));
+    }
+}
@@ -0,0 +1,325 @@
+use std::sync::{Arc, OnceLock};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    application_context_trait::ApplicationContextTrait, request_context_trait::RequestContextTrait,
+};
+
+use super::{
+    template::{ParamType, Segment},
+    RouteInfo, Router,
+};
+
+/// Documentation of a route, attached with [`super::RouterBuilder::metadata`] and used by
+/// [`OpenApi`]. Schemas are JSON Schema values, e.g. `json!({ "type": "string" })`.
+#[derive(Clone, Debug, Default)]
+pub struct RouteMetadata {
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    /// Overrides the schemas derived from the parameter types of the template.
+    pub param_schemas: Vec<(String, Value)>,
+    /// Name, schema, and whether the query parameter is required.
+    pub query_params: Vec<(String, Value, bool)>,
+    /// Content type and schema of the request body.
+    pub request_body: Option<(String, Value)>,
+    pub responses: Vec<ResponseMetadata>,
+    /// Leaves the route out of the OpenAPI document.
+    pub hidden: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct ResponseMetadata {
+    pub status: hyper::StatusCode,
+    pub description: String,
+    /// Content type and schema of the response body.
+    pub body: Option<(String, Value)>,
+}
+
+impl RouteMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn summary(mut self, summary: &str) -> Self {
+        self.summary = Some(summary.to_string());
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    pub fn param_schema(mut self, name: &str, schema: Value) -> Self {
+        self.param_schemas.push((name.to_string(), schema));
+        self
+    }
+
+    pub fn query_param(mut self, name: &str, schema: Value, required: bool) -> Self {
+        self.query_params.push((name.to_string(), schema, required));
+        self
+    }
+
+    pub fn request_body(mut self, content_type: &str, schema: Value) -> Self {
+        self.request_body = Some((content_type.to_string(), schema));
+        self
+    }
+
+    pub fn response(mut self, status: hyper::StatusCode, description: &str) -> Self {
+        self.responses.push(ResponseMetadata {
+            status,
+            description: description.to_string(),
+            body: None,
+        });
+        self
+    }
+
+    pub fn response_body(
+        mut self,
+        status: hyper::StatusCode,
+        description: &str,
+        content_type: &str,
+        schema: Value,
+    ) -> Self {
+        self.responses.push(ResponseMetadata {
+            status,
+            description: description.to_string(),
+            body: Some((content_type.to_string(), schema)),
+        });
+        self
+    }
+
+    pub fn hidden(mut self) -> Self {
+        self.hidden = true;
+        self
+    }
+}
+
+/// Generates an OpenAPI 3.1 document of the template routes of a router, see
+/// [`super::RouterBuilder::openapi`] to serve it.
+///
+/// Path parameters are described by the types of the template, everything else comes from the
+/// [`RouteMetadata`] of the routes. Regex routes are left out, they have no parameter names.
+#[derive(Clone, Debug)]
+pub struct OpenApi {
+    title: String,
+    version: String,
+    description: Option<String>,
+    servers: Vec<String>,
+}
+
+impl OpenApi {
+    pub fn new(title: &str, version: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            version: version.to_string(),
+            description: None,
+            servers: Vec::new(),
+        }
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn server(mut self, url: &str) -> Self {
+        self.servers.push(url.to_string());
+        self
+    }
+
+    pub fn document<
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+    >(
+        &self,
+        router: &Router<ApplicationContextType, RequestContextType>,
+    ) -> Value {
+        let mut paths = Map::new();
+        for route in router.routes() {
+            let Some(template) = route.template.filter(|_| !route.metadata.hidden) else {
+                continue;
+            };
+
+            let path = openapi_path(template.segments());
+            let path_item = paths
+                .entry(path)
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut()
+                .expect("path items are objects");
+            for method in route.methods.iter() {
+                let method = method.as_str().to_ascii_lowercase();
+                // the operations of routes narrowed down by guards are not repeated
+                if OPERATION_METHODS.contains(&method.as_str()) && !path_item.contains_key(&method)
+                {
+                    path_item.insert(method, operation(&route, template.segments()));
+                }
+            }
+        }
+
+        let mut info = json!({
+            "title": self.title,
+            "version": self.version,
+        });
+        if let Some(description) = &self.description {
+            info["description"] = json!(description);
+        }
+
+        let mut document = json!({
+            "openapi": "3.1.0",
+            "info": info,
+            "paths": paths,
+        });
+        if !self.servers.is_empty() {
+            document["servers"] = self
+                .servers
+                .iter()
+                .map(|url| json!({ "url": url }))
+                .collect();
+        }
+
+        document
+    }
+}
+
+/// An [`OpenApi`] served by a route, the document is generated when the router is built.
+pub(super) struct OpenApiEndpoint {
+    pub openapi: OpenApi,
+    pub document: Arc<OnceLock<String>>,
+}
+
+/// The methods an OpenAPI path item has operations for.
+const OPERATION_METHODS: &[&str] = &[
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// The template without parameter types, e.g. `/users/{id}` for `/users/{id:u64}`.
+fn openapi_path(segments: &[Segment]) -> String {
+    let mut path = String::new();
+    for segment in segments {
+        path.push('/');
+        match segment {
+            Segment::Static(segment) => path.push_str(segment),
+            Segment::Param { name, .. } | Segment::Wildcard { name } => {
+                path.push('{');
+                path.push_str(name);
+                path.push('}');
+            }
+        }
+    }
+    if path.is_empty() {
+        path.push('/');
+    }
+
+    path
+}
+
+fn operation(route: &RouteInfo<'_>, segments: &[Segment]) -> Value {
+    let metadata = route.metadata;
+    let mut operation = Map::new();
+
+    if let Some(name) = route.name {
+        operation.insert("operationId".into(), json!(name));
+    }
+    if let Some(summary) = &metadata.summary {
+        operation.insert("summary".into(), json!(summary));
+    }
+    if let Some(description) = &metadata.description {
+        operation.insert("description".into(), json!(description));
+    }
+    if !metadata.tags.is_empty() {
+        operation.insert("tags".into(), json!(metadata.tags));
+    }
+
+    let mut parameters = Vec::new();
+    for segment in segments {
+        let (name, schema) = match segment {
+            Segment::Static(_) => continue,
+            Segment::Param { name, param_type } => (name, param_schema(*param_type)),
+            Segment::Wildcard { name } => (name, param_schema(ParamType::Str)),
+        };
+        let schema = metadata
+            .param_schemas
+            .iter()
+            .find(|(param_name, _)| param_name == name)
+            .map_or(schema, |(_, schema)| schema.clone());
+        parameters.push(json!({
+            "name": name,
+            "in": "path",
+            "required": true,
+            "schema": schema,
+        }));
+    }
+    for (name, schema, required) in metadata.query_params.iter() {
+        parameters.push(json!({
+            "name": name,
+            "in": "query",
+            "required": required,
+            "schema": schema,
+        }));
+    }
+    if !parameters.is_empty() {
+        operation.insert("parameters".into(), Value::Array(parameters));
+    }
+
+    if let Some((content_type, schema)) = &metadata.request_body {
+        operation.insert(
+            "requestBody".into(),
+            json!({
+                "required": true,
+                "content": { content_type: { "schema": schema } },
+            }),
+        );
+    }
+
+    if !metadata.responses.is_empty() {
+        let mut responses = Map::new();
+        for response in metadata.responses.iter() {
+            let mut response_object = json!({ "description": response.description });
+            if let Some((content_type, schema)) = &response.body {
+                response_object["content"] = json!({ content_type: { "schema": schema } });
+            }
+            responses.insert(response.status.as_str().to_string(), response_object);
+        }
+        operation.insert("responses".into(), Value::Object(responses));
+    }
+
+    Value::Object(operation)
+}
+
+fn param_schema(param_type: ParamType) -> Value {
+    match param_type {
+        ParamType::Str => json!({ "type": "string" }),
+        ParamType::U32 => json!({ "type": "integer", "minimum": 0, "maximum": u32::MAX }),
+        ParamType::U64 | ParamType::Usize => json!({ "type": "integer", "minimum": 0 }),
+        ParamType::I32 => json!({ "type": "integer", "format": "int32" }),
+        ParamType::I64 => json!({ "type": "integer", "format": "int64" }),
+        ParamType::Uuid => json!({ "type": "string", "format": "uuid" }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::routing::RouteTemplate;
+
+    #[test]
+    fn paths_without_param_types() {
+        for (template, path) in [
+            ("/", "/"),
+            ("/users/{id:u64}/posts/{slug}", "/users/{id}/posts/{slug}"),
+            ("/files/{*path}", "/files/{path}"),
+        ] {
+            let template = RouteTemplate::parse(template).unwrap();
+            assert_eq!(openapi_path(template.segments()), path);
+        }
+    }
+}
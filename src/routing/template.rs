@@ -0,0 +1,380 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    content_type::ContentType, request_handler::ErrorResponse, response::create_string_response,
+};
+
+use super::RoutingError;
+
+/// The type a path parameter is checked against before the request handler is called, written
+/// after the parameter name, e.g. `{user_id:u64}`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParamType {
+    /// Any non-empty segment, the default if no type is given.
+    Str,
+    U32,
+    U64,
+    I32,
+    I64,
+    Usize,
+    /// Hyphenated hexadecimal UUID, e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+    Uuid,
+}
+
+impl ParamType {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "str" => Some(ParamType::Str),
+            "u32" => Some(ParamType::U32),
+            "u64" => Some(ParamType::U64),
+            "i32" => Some(ParamType::I32),
+            "i64" => Some(ParamType::I64),
+            "usize" => Some(ParamType::Usize),
+            "uuid" => Some(ParamType::Uuid),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ParamType::Str => "str",
+            ParamType::U32 => "u32",
+            ParamType::U64 => "u64",
+            ParamType::I32 => "i32",
+            ParamType::I64 => "i64",
+            ParamType::Usize => "usize",
+            ParamType::Uuid => "uuid",
+        }
+    }
+
+    pub fn accepts(self, value: &str) -> bool {
+        match self {
+            ParamType::Str => !value.is_empty(),
+            ParamType::U32 => value.parse::<u32>().is_ok(),
+            ParamType::U64 => value.parse::<u64>().is_ok(),
+            ParamType::I32 => value.parse::<i32>().is_ok(),
+            ParamType::I64 => value.parse::<i64>().is_ok(),
+            ParamType::Usize => value.parse::<usize>().is_ok(),
+            ParamType::Uuid => is_uuid(value),
+        }
+    }
+}
+
+fn is_uuid(value: &str) -> bool {
+    let groups: Vec<&str> = value.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum Segment {
+    Static(String),
+    Param {
+        name: String,
+        param_type: ParamType,
+    },
+    /// Matches the rest of the path, at least one character, e.g. `{*path}`.
+    Wildcard {
+        name: String,
+    },
+}
+
+/// A route path with named parameters, e.g. `/users/{user_id:u64}/posts/{slug}`.
+///
+/// Parameters span a whole segment. Their value is checked against their [`ParamType`]
+/// before the request handler is called, requests with a parameter of the wrong type are
+/// answered with `400 Bad Request`. The last segment may be a wildcard, e.g.
+/// `/static/{*path}`, which captures the rest of the path including its slashes.
+#[derive(Clone, Debug)]
+pub struct RouteTemplate {
+    template: String,
+    segments: Vec<Segment>,
+}
+
+impl RouteTemplate {
+    pub fn parse(template: &str) -> Result<Self, RoutingError> {
+        let invalid = |reason: String| RoutingError::InvalidTemplate {
+            template: template.to_string(),
+            reason,
+        };
+
+        let path = template
+            .strip_prefix('/')
+            .ok_or_else(|| invalid("it has to start with '/'".into()))?;
+
+        let mut segments = Vec::new();
+        let mut names: Vec<&str> = Vec::new();
+        for segment in split_segments(path) {
+            if matches!(segments.last(), Some(Segment::Wildcard { .. })) {
+                return Err(invalid("the wildcard has to be the last segment".into()));
+            }
+
+            let param = segment
+                .strip_prefix('{')
+                .and_then(|segment| segment.strip_suffix('}'));
+
+            match param {
+                Some(param) => {
+                    let (name, param_type) = match param.split_once(':') {
+                        Some((name, type_name)) => (
+                            name,
+                            Some(ParamType::from_name(type_name).ok_or_else(|| {
+                                invalid(format!("unknown parameter type '{type_name}'"))
+                            })?),
+                        ),
+                        None => (param, None),
+                    };
+                    let (name, is_wildcard) = match name.strip_prefix('*') {
+                        Some(name) => (name, true),
+                        None => (name, false),
+                    };
+
+                    if name.is_empty()
+                        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        return Err(invalid(format!("invalid parameter name '{name}'")));
+                    }
+                    if names.contains(&name) {
+                        return Err(invalid(format!("duplicate parameter '{name}'")));
+                    }
+                    names.push(name);
+
+                    segments.push(match (is_wildcard, param_type) {
+                        (true, Some(_)) => {
+                            return Err(invalid(format!(
+                                "the wildcard '{name}' cannot have a type"
+                            )))
+                        }
+                        (true, None) => Segment::Wildcard {
+                            name: name.to_string(),
+                        },
+                        (false, param_type) => Segment::Param {
+                            name: name.to_string(),
+                            param_type: param_type.unwrap_or(ParamType::Str),
+                        },
+                    });
+                }
+                None if segment.contains(['{', '}']) => {
+                    return Err(invalid(format!(
+                        "parameters have to span a whole segment, found '{segment}'"
+                    )));
+                }
+                None => segments.push(Segment::Static(segment.to_string())),
+            }
+        }
+
+        Ok(Self {
+            template: template.to_string(),
+            segments,
+        })
+    }
+
+    /// A template without parameters, `path` is matched as it is.
+    pub(super) fn literal(path: &str) -> Option<Self> {
+        let segments = split_segments(path.strip_prefix('/')?)
+            .map(|segment| Segment::Static(segment.to_string()))
+            .collect();
+
+        Some(Self {
+            template: path.to_string(),
+            segments,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// The template mounted under the literal `prefix`, e.g. `/users` under `/admin` is
+    /// `/admin/users` and `/` under `/admin` is `/admin`.
+    pub(super) fn with_prefix(&self, prefix: &RouteTemplate) -> Self {
+        let template = if self.segments.is_empty() {
+            prefix.template.clone()
+        } else if prefix.segments.is_empty() {
+            self.template.clone()
+        } else {
+            prefix.template.clone() + &self.template
+        };
+
+        Self {
+            template,
+            segments: prefix
+                .segments
+                .iter()
+                .chain(self.segments.iter())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// The template with the parameter names left out, templates with the same canonical form
+    /// match the same paths.
+    pub(super) fn canonical(&self) -> String {
+        let mut canonical = String::new();
+        for segment in self.segments.iter() {
+            canonical.push('/');
+            match segment {
+                Segment::Static(segment) => canonical.push_str(segment),
+                Segment::Param { param_type, .. } => {
+                    canonical.push('{');
+                    canonical.push_str(param_type.name());
+                    canonical.push('}');
+                }
+                Segment::Wildcard { .. } => canonical.push_str("{*}"),
+            }
+        }
+        if canonical.is_empty() {
+            canonical.push('/');
+        }
+        canonical
+    }
+
+    pub(super) fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+}
+
+/// The segments of a path without its leading '/', the root path has none.
+pub(super) fn split_segments(path: &str) -> impl Iterator<Item = &str> {
+    let mut segments = path.split('/');
+    if path.is_empty() {
+        segments.next();
+    }
+    segments
+}
+
+/// The parameters extracted from the request path by a [`RouteTemplate`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PathParams(pub(super) Vec<(String, String)>);
+
+impl PathParams {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(param_name, _)| param_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Parses the parameter into any [`FromStr`] type. The error converts into a
+    /// `400 Bad Request` [`ErrorResponse`], so handlers can use `?`.
+    pub fn parse<T: FromStr>(&self, name: &str) -> Result<T, PathParamError> {
+        let value = self
+            .get(name)
+            .ok_or_else(|| PathParamError::Missing(name.to_string()))?;
+
+        value.parse().map_err(|_| PathParamError::Invalid {
+            name: name.to_string(),
+            value: value.to_string(),
+            expected: std::any::type_name::<T>().to_string(),
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathParamError {
+    Missing(String),
+    Invalid {
+        name: String,
+        value: String,
+        expected: String,
+    },
+}
+
+impl Display for PathParamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathParamError::Missing(name) => write!(f, "missing path parameter '{name}'"),
+            PathParamError::Invalid {
+                name,
+                value,
+                expected,
+            } => write!(
+                f,
+                "invalid path parameter '{name}', '{value}' is not a valid {expected}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PathParamError {}
+
+impl From<PathParamError> for ErrorResponse {
+    fn from(e: PathParamError) -> Self {
+        create_string_response(hyper::StatusCode::BAD_REQUEST, e, ContentType::TextPlain).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_segments() {
+        let template = RouteTemplate::parse("/users/{user_id:u64}/files/{*path}").unwrap();
+
+        assert_eq!(
+            template.segments(),
+            [
+                Segment::Static("users".into()),
+                Segment::Param {
+                    name: "user_id".into(),
+                    param_type: ParamType::U64
+                },
+                Segment::Static("files".into()),
+                Segment::Wildcard {
+                    name: "path".into()
+                },
+            ]
+        );
+        assert!(RouteTemplate::parse("/").unwrap().segments().is_empty());
+    }
+
+    #[test]
+    fn prefixed_template() {
+        let prefix = RouteTemplate::literal("/admin").unwrap();
+
+        let template = RouteTemplate::parse("/users/{id:u64}")
+            .unwrap()
+            .with_prefix(&prefix);
+        assert_eq!(template.as_str(), "/admin/users/{id:u64}");
+        assert_eq!(template.canonical(), "/admin/users/{u64}");
+
+        let template = RouteTemplate::parse("/").unwrap().with_prefix(&prefix);
+        assert_eq!(template.as_str(), "/admin");
+        assert_eq!(template.canonical(), "/admin");
+    }
+
+    #[test]
+    fn uuid_param_type() {
+        assert!(ParamType::Uuid.accepts("67e55044-10b1-426f-9247-bb680e5fe0c8"));
+        assert!(!ParamType::Uuid.accepts("67e55044"));
+        assert!(!ParamType::Uuid.accepts("67e55044-10b1-426f-9247-bb680e5fe0cx"));
+    }
+
+    #[test]
+    fn invalid_templates() {
+        for template in [
+            "users",
+            "/users/{id:float}",
+            "/users/{}",
+            "/users/{id}/{id}",
+            "/files/{name}.txt",
+            "/files/{*path}/more",
+            "/files/{*path:u64}",
+        ] {
+            assert!(
+                RouteTemplate::parse(template).is_err(),
+                "{template} should be rejected"
+            );
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use super::template::{
+    split_segments, ParamType, PathParamError, PathParams, RouteTemplate, Segment,
+};
+
+/// Prefix tree of route templates, one node per path segment.
+///
+/// Lookup walks the path segment by segment and tries static children before parameters and
+/// parameters before wildcards, backtracking when a branch does not lead to a route. The cost
+/// depends on the length of the path instead of the number of routes.
+#[derive(Default)]
+pub(super) struct RouteTree {
+    root: Node,
+    /// Static segments are stored and looked up in ASCII lowercase.
+    case_insensitive: bool,
+}
+
+#[derive(Default)]
+struct Node {
+    static_children: HashMap<String, Node>,
+    param_children: Vec<ParamNode>,
+    wildcard: Option<WildcardNode>,
+    /// Indices of the routes ending at this node, in the order they were added.
+    routes: Vec<usize>,
+}
+
+struct ParamNode {
+    name: String,
+    param_type: ParamType,
+    node: Node,
+}
+
+/// The routes ending in a wildcard at this position, with the name each of them gave it.
+#[derive(Default)]
+struct WildcardNode {
+    routes: Vec<(usize, String)>,
+}
+
+pub(super) enum TreeMatch {
+    Matched(usize, PathParams),
+    /// The path has the shape of a route, but a typed parameter did not parse.
+    InvalidParam(PathParamError),
+    NoMatch,
+}
+
+impl RouteTree {
+    pub fn new(case_insensitive: bool) -> Self {
+        Self {
+            root: Node::default(),
+            case_insensitive,
+        }
+    }
+
+    pub fn insert(&mut self, template: &RouteTemplate, route: usize) {
+        let mut node = &mut self.root;
+        for segment in template.segments() {
+            node = match segment {
+                Segment::Static(segment) => {
+                    let segment = if self.case_insensitive {
+                        segment.to_ascii_lowercase()
+                    } else {
+                        segment.clone()
+                    };
+                    node.static_children.entry(segment).or_default()
+                }
+                Segment::Param { name, param_type } => {
+                    let index =
+                        match node.param_children.iter().position(|child| {
+                            &child.name == name && child.param_type == *param_type
+                        }) {
+                            Some(index) => index,
+                            None => {
+                                node.param_children.push(ParamNode {
+                                    name: name.clone(),
+                                    param_type: *param_type,
+                                    node: Node::default(),
+                                });
+                                node.param_children.len() - 1
+                            }
+                        };
+                    &mut node.param_children[index].node
+                }
+                Segment::Wildcard { name } => {
+                    node.wildcard
+                        .get_or_insert_with(WildcardNode::default)
+                        .routes
+                        .push((route, name.clone()));
+                    return;
+                }
+            };
+        }
+
+        node.routes.push(route);
+    }
+
+    /// Finds the route for `path`, `accepts` filters the candidates, e.g. by method.
+    pub fn find(&self, path: &str, accepts: impl Fn(usize) -> bool) -> TreeMatch {
+        let path = match path.strip_prefix('/') {
+            Some(path) => path,
+            None => return TreeMatch::NoMatch,
+        };
+
+        let mut search = Search {
+            path,
+            segments: split_segments(path).collect(),
+            case_insensitive: self.case_insensitive,
+            accepts,
+            params: Vec::new(),
+            invalid_param: None,
+        };
+
+        match search.visit(&self.root, 0, None) {
+            Some(route) => TreeMatch::Matched(route, PathParams(search.params)),
+            None => match search.invalid_param {
+                Some(invalid_param) => TreeMatch::InvalidParam(invalid_param),
+                None => TreeMatch::NoMatch,
+            },
+        }
+    }
+}
+
+struct Search<'a, AcceptsFn: Fn(usize) -> bool> {
+    path: &'a str,
+    segments: Vec<&'a str>,
+    case_insensitive: bool,
+    accepts: AcceptsFn,
+    params: Vec<(String, String)>,
+    /// The first typed parameter that failed on an otherwise matching route.
+    invalid_param: Option<PathParamError>,
+}
+
+impl<'a, AcceptsFn: Fn(usize) -> bool> Search<'a, AcceptsFn> {
+    fn visit(
+        &mut self,
+        node: &Node,
+        index: usize,
+        invalid_param: Option<&PathParamError>,
+    ) -> Option<usize> {
+        let segment = match self.segments.get(index) {
+            Some(segment) => *segment,
+            None => return self.end_of_path(node.routes.iter().copied(), invalid_param),
+        };
+
+        let child = if self.case_insensitive {
+            node.static_children.get(&segment.to_ascii_lowercase())
+        } else {
+            node.static_children.get(segment)
+        };
+        if let Some(child) = child {
+            if let Some(route) = self.visit(child, index + 1, invalid_param) {
+                return Some(route);
+            }
+        }
+
+        if !segment.is_empty() {
+            for child in node.param_children.iter() {
+                let child_invalid_param = match invalid_param {
+                    Some(invalid_param) => Some(invalid_param.clone()),
+                    None => (!child.param_type.accepts(segment)).then(|| PathParamError::Invalid {
+                        name: child.name.clone(),
+                        value: segment.to_string(),
+                        expected: child.param_type.name().to_string(),
+                    }),
+                };
+
+                self.params.push((child.name.clone(), segment.to_string()));
+                if let Some(route) =
+                    self.visit(&child.node, index + 1, child_invalid_param.as_ref())
+                {
+                    return Some(route);
+                }
+                self.params.pop();
+            }
+        }
+
+        if let Some(wildcard) = &node.wildcard {
+            let rest = self.rest_of_path(index);
+            if !rest.is_empty() {
+                let routes = wildcard.routes.iter().map(|(route, _)| *route);
+                if let Some(route) = self.end_of_path(routes, invalid_param) {
+                    let name = wildcard
+                        .routes
+                        .iter()
+                        .find_map(|(wildcard_route, name)| {
+                            (*wildcard_route == route).then_some(name)
+                        })
+                        .expect("the route was found among the wildcard routes");
+                    self.params.push((name.clone(), rest.to_string()));
+                    return Some(route);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn end_of_path(
+        &mut self,
+        mut routes: impl Iterator<Item = usize>,
+        invalid_param: Option<&PathParamError>,
+    ) -> Option<usize> {
+        let route = routes.find(|route| (self.accepts)(*route))?;
+
+        match invalid_param {
+            Some(invalid_param) => {
+                self.invalid_param
+                    .get_or_insert_with(|| invalid_param.clone());
+                None
+            }
+            None => Some(route),
+        }
+    }
+
+    /// The path from the segment at `index` to the end.
+    fn rest_of_path(&self, index: usize) -> &'a str {
+        let offset: usize = self.segments[..index]
+            .iter()
+            .map(|segment| segment.len() + 1)
+            .sum();
+        &self.path[offset..]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tree(templates: &[&str]) -> RouteTree {
+        let mut tree = RouteTree::default();
+        for (route, template) in templates.iter().enumerate() {
+            tree.insert(&RouteTemplate::parse(template).unwrap(), route);
+        }
+        tree
+    }
+
+    fn find(tree: &RouteTree, path: &str) -> Option<(usize, PathParams)> {
+        match tree.find(path, |_| true) {
+            TreeMatch::Matched(route, params) => Some((route, params)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn static_routes() {
+        let tree = tree(&["/", "/hello/world", "/hello"]);
+
+        assert_eq!(find(&tree, "/").unwrap().0, 0);
+        assert_eq!(find(&tree, "/hello/world").unwrap().0, 1);
+        assert_eq!(find(&tree, "/hello").unwrap().0, 2);
+        assert!(find(&tree, "/hello/").is_none());
+        assert!(find(&tree, "/hello/world/again").is_none());
+        assert!(find(&tree, "hello").is_none());
+    }
+
+    #[test]
+    fn typed_params() {
+        let tree = tree(&["/users/{user_id:u64}/posts/{slug}"]);
+
+        let (_, params) = find(&tree, "/users/42/posts/first-post").unwrap();
+        assert_eq!(params.parse::<u64>("user_id").unwrap(), 42);
+        assert_eq!(params.get("slug"), Some("first-post"));
+        assert!(params.parse::<u8>("slug").is_err());
+        assert_eq!(params.get("missing"), None);
+    }
+
+    #[test]
+    fn invalid_param_value() {
+        let tree = tree(&["/users/{user_id:u64}"]);
+
+        assert!(matches!(
+            tree.find("/users/abc", |_| true),
+            TreeMatch::InvalidParam(PathParamError::Invalid { .. })
+        ));
+        assert!(matches!(tree.find("/users/", |_| true), TreeMatch::NoMatch));
+        assert!(matches!(
+            tree.find("/users/abc/more", |_| true),
+            TreeMatch::NoMatch
+        ));
+    }
+
+    #[test]
+    fn static_beats_param_beats_wildcard() {
+        let tree = tree(&["/files/{*path}", "/files/{name}", "/files/index"]);
+
+        assert_eq!(find(&tree, "/files/index").unwrap().0, 2);
+        assert_eq!(find(&tree, "/files/readme").unwrap().0, 1);
+
+        let (route, params) = find(&tree, "/files/docs/readme.md").unwrap();
+        assert_eq!(route, 0);
+        assert_eq!(params.get("path"), Some("docs/readme.md"));
+        assert!(find(&tree, "/files/").is_none());
+    }
+
+    #[test]
+    fn wildcard_names_per_route() {
+        let tree = tree(&["/static/{*path}", "/static/{*file}"]);
+
+        let TreeMatch::Matched(route, params) =
+            tree.find("/static/css/site.css", |route| route == 1)
+        else {
+            panic!("no match");
+        };
+        assert_eq!(route, 1);
+        assert_eq!(params.get("file"), Some("css/site.css"));
+        assert_eq!(params.get("path"), None);
+    }
+
+    #[test]
+    fn backtracks_to_less_specific_routes() {
+        let tree = tree(&["/users/me/settings", "/users/{id}/posts", "/users/{*rest}"]);
+
+        assert_eq!(find(&tree, "/users/me/posts").unwrap().0, 1);
+        assert_eq!(find(&tree, "/users/me/likes").unwrap().0, 2);
+    }
+
+    #[test]
+    fn valid_route_beats_invalid_param() {
+        let tree = tree(&["/items/{id:u64}", "/items/{name}"]);
+
+        assert_eq!(find(&tree, "/items/ball").unwrap().0, 1);
+        assert_eq!(find(&tree, "/items/7").unwrap().0, 0);
+    }
+
+    #[test]
+    fn case_insensitive_static_segments() {
+        let mut tree = RouteTree::new(true);
+        tree.insert(&RouteTemplate::parse("/Users/{name}").unwrap(), 0);
+
+        let (route, params) = match tree.find("/USERS/Alice", |_| true) {
+            TreeMatch::Matched(route, params) => (route, params),
+            _ => panic!("no match"),
+        };
+        assert_eq!(route, 0);
+        assert_eq!(params.get("name"), Some("Alice"));
+    }
+
+    #[test]
+    fn filtered_routes_are_skipped() {
+        let tree = tree(&["/resource", "/resource", "/{name}"]);
+
+        assert!(matches!(
+            tree.find("/resource", |route| route == 1),
+            TreeMatch::Matched(1, _)
+        ));
+        assert!(matches!(
+            tree.find("/resource", |route| route == 2),
+            TreeMatch::Matched(2, _)
+        ));
+    }
+}
@@ -0,0 +1,272 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{Arc, OnceLock},
+};
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use super::template::{RouteTemplate, Segment};
+
+/// Everything but the unreserved characters of RFC 3986.
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UrlError {
+    /// The router of the [`RouteUrls`] has not been built yet.
+    RouterNotBuilt,
+    UnknownRoute(String),
+    /// The route is a regex, URLs can only be generated for templates.
+    NotATemplate(String),
+    MissingParam {
+        route: String,
+        param: String,
+    },
+    UnknownParam {
+        route: String,
+        param: String,
+    },
+    InvalidParam {
+        route: String,
+        param: String,
+        value: String,
+    },
+}
+
+impl Display for UrlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UrlError::RouterNotBuilt => write!(f, "the router has not been built yet"),
+            UrlError::UnknownRoute(route) => write!(f, "no route named '{route}'"),
+            UrlError::NotATemplate(route) => {
+                write!(f, "route '{route}' is a regex, not a template")
+            }
+            UrlError::MissingParam { route, param } => {
+                write!(f, "missing parameter '{param}' of route '{route}'")
+            }
+            UrlError::UnknownParam { route, param } => {
+                write!(f, "route '{route}' has no parameter '{param}'")
+            }
+            UrlError::InvalidParam {
+                route,
+                param,
+                value,
+            } => write!(
+                f,
+                "invalid value '{value}' for parameter '{param}' of route '{route}'"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UrlError {}
+
+/// The templates of the named routes, `None` for regex routes.
+pub(super) type NamedRoutes = HashMap<String, Option<RouteTemplate>>;
+
+/// Generates the URLs of the named routes of a router, see [`super::RouterBuilder::name`].
+///
+/// It can be taken from the [`super::RouterBuilder`] before the router is built, e.g. to keep it
+/// in the application context, and works once the router has been built.
+#[derive(Clone, Default)]
+pub struct RouteUrls(Arc<OnceLock<Arc<NamedRoutes>>>);
+
+impl RouteUrls {
+    pub(super) fn set(&self, named_routes: Arc<NamedRoutes>) {
+        let _ = self.0.set(named_routes);
+    }
+
+    pub fn url_for(&self, route: &str) -> UrlBuilder<'_> {
+        UrlBuilder {
+            route_urls: self,
+            route: route.to_string(),
+            params: Vec::new(),
+            query: Vec::new(),
+        }
+    }
+}
+
+pub struct UrlBuilder<'a> {
+    route_urls: &'a RouteUrls,
+    route: String,
+    params: Vec<(String, String)>,
+    query: Vec<(String, String)>,
+}
+
+impl UrlBuilder<'_> {
+    /// Fills the path parameter `name` of the template. Wildcard values keep their slashes.
+    pub fn param(mut self, name: &str, value: impl ToString) -> Self {
+        self.params.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Appends a query parameter, in the order they are added.
+    pub fn query(mut self, name: &str, value: impl ToString) -> Self {
+        self.query.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Returns the path and the query of the URL, e.g. `/users/42/posts?page=2`.
+    pub fn build(self) -> Result<String, UrlError> {
+        let named_routes = self.route_urls.0.get().ok_or(UrlError::RouterNotBuilt)?;
+        let template = named_routes
+            .get(&self.route)
+            .ok_or_else(|| UrlError::UnknownRoute(self.route.clone()))?
+            .as_ref()
+            .ok_or_else(|| UrlError::NotATemplate(self.route.clone()))?;
+
+        if let Some((name, _)) = self.params.iter().find(|(name, _)| {
+            !template.segments().iter().any(|segment| match segment {
+                Segment::Param {
+                    name: param_name, ..
+                }
+                | Segment::Wildcard { name: param_name } => param_name == name,
+                Segment::Static(_) => false,
+            })
+        }) {
+            return Err(UrlError::UnknownParam {
+                route: self.route.clone(),
+                param: name.clone(),
+            });
+        }
+
+        let mut url = String::new();
+        for segment in template.segments() {
+            url.push('/');
+            match segment {
+                Segment::Static(segment) => url.push_str(segment),
+                Segment::Param { name, param_type } => {
+                    let value = self.param_value(name)?;
+                    if !param_type.accepts(value) {
+                        return Err(UrlError::InvalidParam {
+                            route: self.route.clone(),
+                            param: name.clone(),
+                            value: value.to_string(),
+                        });
+                    }
+                    url.extend(utf8_percent_encode(value, COMPONENT));
+                }
+                Segment::Wildcard { name } => {
+                    let value = self.param_value(name)?;
+                    let segments: Vec<String> = value
+                        .split('/')
+                        .map(|segment| utf8_percent_encode(segment, COMPONENT).to_string())
+                        .collect();
+                    url.push_str(&segments.join("/"));
+                }
+            }
+        }
+        if url.is_empty() {
+            url.push('/');
+        }
+
+        for (index, (name, value)) in self.query.iter().enumerate() {
+            url.push(if index == 0 { '?' } else { '&' });
+            url.extend(utf8_percent_encode(name, COMPONENT));
+            url.push('=');
+            url.extend(utf8_percent_encode(value, COMPONENT));
+        }
+
+        Ok(url)
+    }
+
+    fn param_value(&self, name: &str) -> Result<&str, UrlError> {
+        self.params
+            .iter()
+            .find(|(param_name, _)| param_name == name)
+            .map(|(_, value)| value.as_str())
+            .ok_or_else(|| UrlError::MissingParam {
+                route: self.route.clone(),
+                param: name.to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn route_urls() -> RouteUrls {
+        let route_urls = RouteUrls::default();
+        route_urls.set(Arc::new(HashMap::from([
+            (
+                "post".to_string(),
+                Some(RouteTemplate::parse("/users/{user_id:u64}/posts/{slug}").unwrap()),
+            ),
+            (
+                "file".to_string(),
+                Some(RouteTemplate::parse("/files/{*path}").unwrap()),
+            ),
+            (
+                "index".to_string(),
+                Some(RouteTemplate::parse("/").unwrap()),
+            ),
+            ("legacy".to_string(), None),
+        ])));
+        route_urls
+    }
+
+    #[test]
+    fn fills_and_encodes_params() {
+        let route_urls = route_urls();
+
+        assert_eq!(
+            route_urls
+                .url_for("post")
+                .param("user_id", 42)
+                .param("slug", "hello world/ä")
+                .query("page", 2)
+                .query("q", "a&b")
+                .build()
+                .unwrap(),
+            "/users/42/posts/hello%20world%2F%C3%A4?page=2&q=a%26b"
+        );
+        assert_eq!(
+            route_urls
+                .url_for("file")
+                .param("path", "docs/read me.md")
+                .build()
+                .unwrap(),
+            "/files/docs/read%20me.md"
+        );
+        assert_eq!(route_urls.url_for("index").build().unwrap(), "/");
+    }
+
+    #[test]
+    fn url_errors() {
+        let route_urls = route_urls();
+
+        assert!(matches!(
+            route_urls.url_for("post").param("user_id", 42).build(),
+            Err(UrlError::MissingParam { param, .. }) if param == "slug"
+        ));
+        assert!(matches!(
+            route_urls
+                .url_for("post")
+                .param("user_id", "me")
+                .param("slug", "x")
+                .build(),
+            Err(UrlError::InvalidParam { param, .. }) if param == "user_id"
+        ));
+        assert!(matches!(
+            route_urls.url_for("index").param("id", 1).build(),
+            Err(UrlError::UnknownParam { param, .. }) if param == "id"
+        ));
+        assert_eq!(
+            route_urls.url_for("missing").build(),
+            Err(UrlError::UnknownRoute("missing".into()))
+        );
+        assert_eq!(
+            route_urls.url_for("legacy").build(),
+            Err(UrlError::NotATemplate("legacy".into()))
+        );
+        assert_eq!(
+            RouteUrls::default().url_for("index").build(),
+            Err(UrlError::RouterNotBuilt)
+        );
+    }
+}
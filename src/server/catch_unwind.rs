@@ -0,0 +1,64 @@
+use std::{
+    any::Any,
+    future::Future,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Resolves to `Err` with the panic payload if polling `future` panics.
+pub(crate) struct CatchUnwind<FutureType> {
+    future: FutureType,
+}
+
+impl<FutureType: Future + Unpin> CatchUnwind<FutureType> {
+    pub(crate) fn new(future: FutureType) -> Self {
+        Self { future }
+    }
+}
+
+impl<FutureType: Future + Unpin> Future for CatchUnwind<FutureType> {
+    type Output = Result<FutureType::Output, Box<dyn Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = &mut self.future;
+        match std::panic::catch_unwind(AssertUnwindSafe(|| Pin::new(future).poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+/// The message of `panic!` calls, a placeholder for other payloads.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&'static str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn panic_is_caught() {
+        let payload = CatchUnwind::new(Box::pin(async {
+            tokio::task::yield_now().await;
+            panic!("boom {}", 42);
+        }))
+        .await
+        .unwrap_err();
+
+        assert_eq!(panic_message(payload.as_ref()), "boom 42");
+    }
+
+    #[tokio::test]
+    async fn output_is_passed_through() {
+        assert_eq!(CatchUnwind::new(Box::pin(async { 42 })).await.unwrap(), 42);
+    }
+}
@@ -0,0 +1,134 @@
+use std::{fmt::Display, io};
+
+use crate::error::Error;
+
+use super::ConnectionInfo;
+
+/// What went wrong on a connection, see [`ConnectionError`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionErrorKind {
+    /// The client closed or reset the connection in the middle of a message. Usually benign.
+    ClientReset,
+    /// The client sent a malformed message.
+    Parse,
+    /// The client was too slow, e.g. the header read or TLS handshake timeout elapsed.
+    Timeout,
+    /// A response body failed or was aborted while it was being sent.
+    Body,
+    /// The request handler or one of its decorators panicked.
+    HandlerPanic,
+    /// The TLS handshake failed.
+    Tls,
+    Other,
+}
+
+/// A failure that ended a connection, reported to the handler set with
+/// [`super::ServerBuilder::connection_error_handler`].
+#[derive(Debug)]
+pub struct ConnectionError {
+    pub kind: ConnectionErrorKind,
+    pub connection_info: ConnectionInfo,
+    pub error: Error,
+}
+
+impl Display for ConnectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} error on connection {} from {}: {}",
+            self.kind, self.connection_info.id, self.connection_info.peer_address, self.error
+        )
+    }
+}
+
+impl ConnectionError {
+    pub(super) fn from_hyper_error(error: hyper::Error, connection_info: ConnectionInfo) -> Self {
+        let kind = if error.is_timeout() {
+            ConnectionErrorKind::Timeout
+        } else if error.is_parse() {
+            ConnectionErrorKind::Parse
+        } else if error.is_incomplete_message()
+            || error.is_canceled()
+            || error.is_closed()
+            || is_client_reset(&error)
+        {
+            ConnectionErrorKind::ClientReset
+        } else if error.is_user() {
+            ConnectionErrorKind::Body
+        } else {
+            ConnectionErrorKind::Other
+        };
+
+        Self {
+            kind,
+            connection_info,
+            error: error.into(),
+        }
+    }
+
+    pub(super) fn from_io_error(
+        kind: ConnectionErrorKind,
+        error: io::Error,
+        connection_info: ConnectionInfo,
+    ) -> Self {
+        let kind = if is_client_reset(&error) {
+            ConnectionErrorKind::ClientReset
+        } else {
+            kind
+        };
+
+        Self {
+            kind,
+            connection_info,
+            error: error.into(),
+        }
+    }
+}
+
+/// The default connection error handler. Client resets are logged on debug level, handler
+/// panics on error level and everything else on warn level.
+pub fn log_connection_error(connection_error: &ConnectionError) {
+    match connection_error.kind {
+        ConnectionErrorKind::ClientReset => log::debug!("{connection_error}"),
+        ConnectionErrorKind::HandlerPanic => log::error!("{connection_error}"),
+        _ => log::warn!("{connection_error}"),
+    }
+}
+
+/// Looks for an io error caused by the peer in the source chain of `error`.
+fn is_client_reset(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if let Some(io_error) = error.downcast_ref::<io::Error>() {
+            if matches!(
+                io_error.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+            ) {
+                return true;
+            }
+        }
+        source = error.source();
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn client_reset_io_error() {
+        assert!(is_client_reset(&io::Error::new(
+            io::ErrorKind::ConnectionReset,
+            "reset"
+        )));
+        assert!(!is_client_reset(&io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid"
+        )));
+    }
+}
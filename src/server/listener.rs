@@ -0,0 +1,435 @@
+use std::{fmt::Display, future::Future, io, net::SocketAddr, pin::Pin};
+
+#[cfg(unix)]
+use std::{
+    os::unix::io::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    path::PathBuf,
+    task::{Context, Poll},
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream},
+};
+
+#[cfg(unix)]
+use tokio::{
+    io::ReadBuf,
+    net::{UnixListener, UnixStream},
+};
+
+/// The file descriptor of the first socket passed with socket activation, see `sd_listen_fds(3)`.
+#[cfg(unix)]
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Address of either end of a connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SocketAddress {
+    Tcp(SocketAddr),
+    /// The path the socket is bound to, `None` for unnamed sockets (e.g. most clients).
+    #[cfg(unix)]
+    Unix(Option<PathBuf>),
+}
+
+impl Display for SocketAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SocketAddress::Tcp(address) => write!(f, "{address}"),
+            #[cfg(unix)]
+            SocketAddress::Unix(Some(path)) => write!(f, "unix:{}", path.display()),
+            #[cfg(unix)]
+            SocketAddress::Unix(None) => write!(f, "unix:(unnamed)"),
+        }
+    }
+}
+
+pub type AcceptFuture<'a, StreamType> =
+    Pin<Box<dyn Future<Output = Result<(StreamType, SocketAddress), io::Error>> + Send + 'a>>;
+
+/// A connection accepted by a [`Listener`].
+pub trait ListenerStream: AsyncRead + AsyncWrite + Unpin + Send + 'static {
+    /// The address of this end of the connection.
+    fn local_address(&self) -> Result<SocketAddress, io::Error>;
+
+    /// Only meaningful for TCP streams, the other streams ignore it.
+    fn set_nodelay(&self, _nodelay: bool) -> Result<(), io::Error> {
+        Ok(())
+    }
+}
+
+/// A bound socket the server accepts its connections from.
+pub trait Listener: Send + Sync + 'static {
+    type Stream: ListenerStream;
+
+    /// Returns the stream of the next connection and the address of the peer.
+    fn accept(&self) -> AcceptFuture<'_, Self::Stream>;
+
+    fn local_address(&self) -> Result<SocketAddress, io::Error>;
+}
+
+impl ListenerStream for TcpStream {
+    fn local_address(&self) -> Result<SocketAddress, io::Error> {
+        Ok(SocketAddress::Tcp(self.local_addr()?))
+    }
+
+    fn set_nodelay(&self, nodelay: bool) -> Result<(), io::Error> {
+        TcpStream::set_nodelay(self, nodelay)
+    }
+}
+
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+
+    fn accept(&self) -> AcceptFuture<'_, Self::Stream> {
+        Box::pin(async move {
+            let (stream, address) = TcpListener::accept(self).await?;
+            Ok((stream, SocketAddress::Tcp(address)))
+        })
+    }
+
+    fn local_address(&self) -> Result<SocketAddress, io::Error> {
+        Ok(SocketAddress::Tcp(self.local_addr()?))
+    }
+}
+
+#[cfg(unix)]
+impl ListenerStream for UnixStream {
+    fn local_address(&self) -> Result<SocketAddress, io::Error> {
+        Ok(SocketAddress::Unix(
+            self.local_addr()?.as_pathname().map(PathBuf::from),
+        ))
+    }
+}
+
+#[cfg(unix)]
+impl Listener for UnixListener {
+    type Stream = UnixStream;
+
+    fn accept(&self) -> AcceptFuture<'_, Self::Stream> {
+        Box::pin(async move {
+            let (stream, address) = UnixListener::accept(self).await?;
+            Ok((
+                stream,
+                SocketAddress::Unix(address.as_pathname().map(PathBuf::from)),
+            ))
+        })
+    }
+
+    fn local_address(&self) -> Result<SocketAddress, io::Error> {
+        Ok(SocketAddress::Unix(
+            self.local_addr()?.as_pathname().map(PathBuf::from),
+        ))
+    }
+}
+
+/// A listener passed to the process by its supervisor, see [`inherited_listeners`].
+#[cfg(unix)]
+pub enum InheritedListener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+#[cfg(unix)]
+pub enum InheritedStream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+/// Takes over the listening sockets passed with systemd-style socket activation (`LISTEN_PID`
+/// and `LISTEN_FDS`), in file descriptor order. Must be called from within a tokio runtime.
+///
+/// Returns an empty list if no sockets were passed to this process, or if they were already
+/// taken over by an earlier call. The environment is left as it is, child processes ignore
+/// the variables because `LISTEN_PID` is not theirs, and the sockets are closed on `exec`.
+/// Each socket has to be a listening TCP or Unix stream socket. On error the passed sockets
+/// stay open, and a later call tries to take them over again.
+#[cfg(unix)]
+pub fn inherited_listeners() -> Result<Vec<InheritedListener>, io::Error> {
+    static TAKEN: parking_lot::Mutex<bool> = parking_lot::Mutex::new(false);
+
+    let (listen_pid, listen_fds) = match (std::env::var("LISTEN_PID"), std::env::var("LISTEN_FDS"))
+    {
+        (Ok(listen_pid), Ok(listen_fds)) => (listen_pid, listen_fds),
+        _ => return Ok(Vec::new()),
+    };
+
+    if listen_pid.parse::<u32>().ok() != Some(std::process::id()) {
+        return Ok(Vec::new());
+    }
+
+    let listen_fds: RawFd = listen_fds.parse().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid LISTEN_FDS value: {listen_fds}"),
+        )
+    })?;
+
+    let mut taken = TAKEN.lock();
+    if *taken {
+        return Ok(Vec::new());
+    }
+
+    let fds = LISTEN_FDS_START..LISTEN_FDS_START + listen_fds;
+    let listeners = fds
+        .clone()
+        // SAFETY: the supervisor passed these sockets to this process, nothing else owns them
+        // until they are taken over below.
+        .map(|fd| InheritedListener::duplicate(unsafe { BorrowedFd::borrow_raw(fd) }))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for fd in fds {
+        // SAFETY: see above, the listeners use duplicates of the passed sockets.
+        drop(unsafe { OwnedFd::from_raw_fd(fd) });
+    }
+    *taken = true;
+
+    Ok(listeners)
+}
+
+#[cfg(unix)]
+impl InheritedListener {
+    /// Listens on a duplicate of `fd`, which is closed on `exec`. `fd` itself is left as it
+    /// is, also on error.
+    fn duplicate(fd: BorrowedFd<'_>) -> Result<Self, io::Error> {
+        let family = listening_stream_socket_family(fd.as_raw_fd())?;
+        let fd = fd.try_clone_to_owned()?;
+
+        match family {
+            libc::AF_INET | libc::AF_INET6 => {
+                let listener = std::net::TcpListener::from(fd);
+                listener.set_nonblocking(true)?;
+                Ok(InheritedListener::Tcp(TcpListener::from_std(listener)?))
+            }
+            _ => {
+                let listener = std::os::unix::net::UnixListener::from(fd);
+                listener.set_nonblocking(true)?;
+                Ok(InheritedListener::Unix(UnixListener::from_std(listener)?))
+            }
+        }
+    }
+}
+
+/// The address family of `fd`, which has to be a listening TCP or Unix stream socket.
+#[cfg(unix)]
+fn listening_stream_socket_family(fd: RawFd) -> Result<libc::c_int, io::Error> {
+    let invalid = |what: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("inherited file descriptor {fd} is not {what}"),
+        )
+    };
+
+    if socket_option(fd, libc::SO_TYPE)? != libc::SOCK_STREAM {
+        return Err(invalid("a stream socket"));
+    }
+    if socket_option(fd, libc::SO_ACCEPTCONN)? == 0 {
+        return Err(invalid("a listening socket"));
+    }
+
+    // SAFETY: an all-zero `sockaddr_storage` is valid, and large enough for any address.
+    let mut address: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut length = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    // SAFETY: `address` and `length` describe a writable buffer of `length` bytes.
+    let result = unsafe {
+        libc::getsockname(
+            fd,
+            &mut address as *mut libc::sockaddr_storage as *mut libc::sockaddr,
+            &mut length,
+        )
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    match address.ss_family as libc::c_int {
+        family @ (libc::AF_INET | libc::AF_INET6 | libc::AF_UNIX) => Ok(family),
+        _ => Err(invalid("a TCP or Unix socket")),
+    }
+}
+
+#[cfg(unix)]
+fn socket_option(fd: RawFd, option: libc::c_int) -> Result<libc::c_int, io::Error> {
+    let mut value: libc::c_int = 0;
+    let mut length = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: `value` and `length` describe a writable `c_int`.
+    let result = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            option,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut length,
+        )
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(value)
+}
+
+#[cfg(unix)]
+impl Listener for InheritedListener {
+    type Stream = InheritedStream;
+
+    fn accept(&self) -> AcceptFuture<'_, Self::Stream> {
+        Box::pin(async move {
+            match self {
+                InheritedListener::Tcp(listener) => {
+                    let (stream, address) = Listener::accept(listener).await?;
+                    Ok((InheritedStream::Tcp(stream), address))
+                }
+                InheritedListener::Unix(listener) => {
+                    let (stream, address) = Listener::accept(listener).await?;
+                    Ok((InheritedStream::Unix(stream), address))
+                }
+            }
+        })
+    }
+
+    fn local_address(&self) -> Result<SocketAddress, io::Error> {
+        match self {
+            InheritedListener::Tcp(listener) => listener.local_address(),
+            InheritedListener::Unix(listener) => listener.local_address(),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl ListenerStream for InheritedStream {
+    fn local_address(&self) -> Result<SocketAddress, io::Error> {
+        match self {
+            InheritedStream::Tcp(stream) => stream.local_address(),
+            InheritedStream::Unix(stream) => stream.local_address(),
+        }
+    }
+
+    fn set_nodelay(&self, nodelay: bool) -> Result<(), io::Error> {
+        match self {
+            InheritedStream::Tcp(stream) => stream.set_nodelay(nodelay),
+            InheritedStream::Unix(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl AsyncRead for InheritedStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            InheritedStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            InheritedStream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl AsyncWrite for InheritedStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        match self.get_mut() {
+            InheritedStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            InheritedStream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        match self.get_mut() {
+            InheritedStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            InheritedStream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        match self.get_mut() {
+            InheritedStream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            InheritedStream::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<Result<usize, io::Error>> {
+        match self.get_mut() {
+            InheritedStream::Tcp(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+            InheritedStream::Unix(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            InheritedStream::Tcp(stream) => stream.is_write_vectored(),
+            InheritedStream::Unix(stream) => stream.is_write_vectored(),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::os::unix::io::AsFd;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn inherited_tcp_and_unix_listeners() {
+        let tcp_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let tcp_address = tcp_listener.local_addr().unwrap();
+        let unix_path = std::env::temp_dir().join(format!(
+            "hyper-accelerator-inherited-{}.sock",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&unix_path);
+        let unix_listener = std::os::unix::net::UnixListener::bind(&unix_path).unwrap();
+
+        let tcp_listener = InheritedListener::duplicate(tcp_listener.as_fd()).unwrap();
+        let unix_listener = InheritedListener::duplicate(unix_listener.as_fd()).unwrap();
+
+        assert_eq!(
+            tcp_listener.local_address().unwrap(),
+            SocketAddress::Tcp(tcp_address)
+        );
+        assert_eq!(
+            unix_listener.local_address().unwrap(),
+            SocketAddress::Unix(Some(unix_path.clone()))
+        );
+
+        std::fs::remove_file(unix_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn inherited_socket_checks() {
+        let tcp_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let fd = tcp_listener.as_raw_fd();
+        unsafe { libc::fcntl(fd, libc::F_SETFD, 0) };
+        let listener = InheritedListener::duplicate(tcp_listener.as_fd()).unwrap();
+        let InheritedListener::Tcp(duplicate) = &listener else {
+            panic!("not a TCP listener");
+        };
+        assert_ne!(duplicate.as_raw_fd(), fd);
+        assert_eq!(
+            unsafe { libc::fcntl(duplicate.as_raw_fd(), libc::F_GETFD) } & libc::FD_CLOEXEC,
+            libc::FD_CLOEXEC
+        );
+
+        let udp_socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let e = InheritedListener::duplicate(udp_socket.as_fd()).err();
+        assert_eq!(e.unwrap().kind(), io::ErrorKind::InvalidInput);
+
+        let tcp_stream =
+            std::net::TcpStream::connect(listener.local_address().unwrap().to_string()).unwrap();
+        let e = InheritedListener::duplicate(tcp_stream.as_fd()).err();
+        assert_eq!(e.unwrap().kind(), io::ErrorKind::InvalidInput);
+
+        // The rejected sockets are still open.
+        assert!(udp_socket.local_addr().is_ok());
+        assert!(tcp_stream.peer_addr().is_ok());
+    }
+}
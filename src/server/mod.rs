@@ -0,0 +1,1018 @@
+pub(crate) mod catch_unwind;
+mod connection_error;
+mod listener;
+mod rewind;
+#[cfg(feature = "tls")]
+mod tls;
+mod tokio_rt;
+
+use std::{
+    any::Any,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use hyper::{
+    server::conn::{http1, http2},
+    service::service_fn,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, ToSocketAddrs},
+    sync::{watch, OwnedSemaphorePermit, Semaphore},
+    task::{JoinError, JoinHandle, JoinSet},
+};
+
+use crate::{
+    app_loop_state::AppLoopStateWatcher,
+    application_context_trait::ApplicationContextTrait,
+    error::Error,
+    request_context_trait::RequestContextTrait,
+    request_handler::{ErrorResponse, Request, RequestHandlerFn, Response},
+    response::create_empty_response,
+    response_body::ResponseBody,
+};
+
+pub use self::connection_error::{log_connection_error, ConnectionError, ConnectionErrorKind};
+#[cfg(unix)]
+pub use self::listener::{inherited_listeners, InheritedListener, InheritedStream};
+pub use self::listener::{AcceptFuture, Listener, ListenerStream, SocketAddress};
+
+use self::{
+    catch_unwind::{panic_message, CatchUnwind},
+    rewind::{detect_http2_preface, Rewind},
+    tokio_rt::{ConnectionExecutor, TokioTimer},
+};
+
+#[cfg(feature = "tls")]
+use self::tls::TlsAcceptor;
+#[cfg(feature = "tls")]
+pub use self::tls::{TlsConfig, TlsError};
+
+/// hyper refuses read buffers smaller than this, see `http1::Builder::max_buf_size`.
+const MINIMUM_MAX_BUF_SIZE: usize = 8192;
+
+/// How long accepting pauses after the process ran out of file descriptors or memory.
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+/// See [`ServerBuilder::tls_handshake_timeout`].
+#[cfg(feature = "tls")]
+pub const DEFAULT_TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub async fn run_http1_tcp_server<
+    SocketAddressType: ToSocketAddrs,
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+    ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+    RequestHandlerFnType: RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+>(
+    listener_address: SocketAddressType,
+    request_handler: RequestHandlerFnType,
+    app_context: ApplicationContextType,
+) -> Result<ServerHandle, io::Error> {
+    ServerBuilder::new()
+        .serve(listener_address, request_handler, app_context)
+        .await
+}
+
+/// Same as [`run_http1_tcp_server`], but serves HTTP/2 with prior knowledge.
+pub async fn run_http2_tcp_server<
+    SocketAddressType: ToSocketAddrs,
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+    ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+    RequestHandlerFnType: RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+>(
+    listener_address: SocketAddressType,
+    request_handler: RequestHandlerFnType,
+    app_context: ApplicationContextType,
+) -> Result<ServerHandle, io::Error> {
+    ServerBuilder::new()
+        .protocol(HttpProtocol::Http2)
+        .serve(listener_address, request_handler, app_context)
+        .await
+}
+
+/// Same as [`run_http1_tcp_server`], but serves both HTTP/1.1 and HTTP/2, see
+/// [`HttpProtocol::Auto`].
+pub async fn run_auto_http_tcp_server<
+    SocketAddressType: ToSocketAddrs,
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+    ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+    RequestHandlerFnType: RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+>(
+    listener_address: SocketAddressType,
+    request_handler: RequestHandlerFnType,
+    app_context: ApplicationContextType,
+) -> Result<ServerHandle, io::Error> {
+    ServerBuilder::new()
+        .protocol(HttpProtocol::Auto)
+        .serve(listener_address, request_handler, app_context)
+        .await
+}
+
+/// Same as [`run_http1_tcp_server`], but the server stops when `app_loop_state_watcher` signals
+/// quit.
+///
+/// After the quit signal no new connections are accepted, the open connections finish the
+/// request they are serving and then get closed. The returned task resolves once every
+/// connection has been drained, or once `drain_timeout` elapsed, in which case the remaining
+/// connections are force-closed.
+pub async fn run_http1_tcp_server_with_graceful_shutdown<
+    SocketAddressType: ToSocketAddrs,
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+    ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+    RequestHandlerFnType: RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+>(
+    listener_address: SocketAddressType,
+    request_handler: RequestHandlerFnType,
+    app_context: ApplicationContextType,
+    app_loop_state_watcher: AppLoopStateWatcher,
+    drain_timeout: Duration,
+) -> Result<ServerHandle, io::Error> {
+    ServerBuilder::new()
+        .graceful_shutdown(app_loop_state_watcher, drain_timeout)
+        .serve(listener_address, request_handler, app_context)
+        .await
+}
+
+/// Same as [`run_http1_tcp_server`], but terminates TLS on every connection. HTTP/1.1 and HTTP/2
+/// are both served, the version is negotiated with ALPN.
+#[cfg(feature = "tls")]
+pub async fn run_https_tcp_server<
+    SocketAddressType: ToSocketAddrs,
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+    ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+    RequestHandlerFnType: RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+>(
+    listener_address: SocketAddressType,
+    request_handler: RequestHandlerFnType,
+    app_context: ApplicationContextType,
+    tls_config: TlsConfig,
+) -> Result<ServerHandle, io::Error> {
+    ServerBuilder::new()
+        .protocol(HttpProtocol::Auto)
+        .tls(tls_config)
+        .serve(listener_address, request_handler, app_context)
+        .await
+}
+
+/// The HTTP version spoken on accepted connections.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpProtocol {
+    Http1,
+    /// HTTP/2 with prior knowledge (h2c), the client has to start with the connection preface.
+    Http2,
+    /// HTTP/2 if the client starts with the HTTP/2 connection preface, HTTP/1.1 otherwise. On TLS
+    /// connections the version negotiated with ALPN takes precedence.
+    Auto,
+}
+
+impl HttpProtocol {
+    #[cfg(feature = "tls")]
+    fn alpn_protocols(self) -> Vec<Vec<u8>> {
+        match self {
+            HttpProtocol::Http1 => vec![b"http/1.1".to_vec()],
+            HttpProtocol::Http2 => vec![b"h2".to_vec()],
+            HttpProtocol::Auto => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+        }
+    }
+}
+
+/// Details of the TLS session, available as a request extension on connections served with TLS.
+#[derive(Clone, Debug)]
+pub struct TlsInfo {
+    /// The server name the client asked for through SNI.
+    pub server_name: Option<String>,
+    pub alpn_protocol: Option<Vec<u8>>,
+    /// DER encoded certificate chain of the client, empty if the client did not authenticate.
+    pub peer_certificates: Vec<Vec<u8>>,
+}
+
+/// The running server, resolves when the server stopped.
+pub struct ServerHandle {
+    local_address: SocketAddress,
+    task: JoinHandle<Result<(), io::Error>>,
+}
+
+impl ServerHandle {
+    /// The address the server is listening on, e.g. the port picked for port 0.
+    pub fn local_address(&self) -> &SocketAddress {
+        &self.local_address
+    }
+
+    /// Stops the server immediately, open connections are closed without draining.
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl Future for ServerHandle {
+    type Output = Result<Result<(), io::Error>, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.task).poll(cx)
+    }
+}
+
+/// Details of the connection a request arrived on, available as a request extension.
+#[derive(Clone, Debug)]
+pub struct ConnectionInfo {
+    /// Unique among the connections accepted by the same server, in accept order.
+    pub id: u64,
+    pub peer_address: SocketAddress,
+    pub local_address: SocketAddress,
+    pub tls_info: Option<TlsInfo>,
+}
+
+#[derive(Clone)]
+struct ConnectionConfig {
+    protocol: HttpProtocol,
+    header_read_timeout: Option<Duration>,
+    keep_alive: bool,
+    keep_alive_idle_timeout: Option<Duration>,
+    max_header_count: Option<usize>,
+    max_header_size: Option<usize>,
+    half_close: bool,
+    tcp_nodelay: bool,
+    #[cfg(feature = "tls")]
+    tls_handshake_timeout: Duration,
+    connection_error_handler: Arc<dyn Fn(&ConnectionError) + Send + Sync>,
+    panic_response: Arc<dyn Fn() -> Response + Send + Sync>,
+}
+
+/// Configures and starts an HTTP server.
+///
+/// Every setting is applied to each accepted connection. The defaults match hyper's defaults,
+/// i.e. no timeouts and no connection limit, except for the TLS handshake, which times out
+/// after [`DEFAULT_TLS_HANDSHAKE_TIMEOUT`].
+#[derive(Clone)]
+pub struct ServerBuilder {
+    connection_config: ConnectionConfig,
+    max_connections: Option<usize>,
+    graceful_shutdown: Option<(AppLoopStateWatcher, Duration)>,
+    #[cfg(feature = "tls")]
+    tls_config: Option<TlsConfig>,
+}
+
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        ServerBuilder::new()
+    }
+}
+
+impl ServerBuilder {
+    pub fn new() -> Self {
+        Self {
+            connection_config: ConnectionConfig {
+                protocol: HttpProtocol::Http1,
+                header_read_timeout: None,
+                keep_alive: true,
+                keep_alive_idle_timeout: None,
+                max_header_count: None,
+                max_header_size: None,
+                half_close: false,
+                tcp_nodelay: false,
+                #[cfg(feature = "tls")]
+                tls_handshake_timeout: DEFAULT_TLS_HANDSHAKE_TIMEOUT,
+                connection_error_handler: Arc::new(log_connection_error),
+                panic_response: Arc::new(|| {
+                    create_empty_response(hyper::StatusCode::INTERNAL_SERVER_ERROR)
+                }),
+            },
+            max_connections: None,
+            graceful_shutdown: None,
+            #[cfg(feature = "tls")]
+            tls_config: None,
+        }
+    }
+
+    pub fn protocol(mut self, protocol: HttpProtocol) -> Self {
+        self.connection_config.protocol = protocol;
+        self
+    }
+
+    /// Closes the connection if the request headers are not received within `timeout` after
+    /// their first byte arrived.
+    pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_config.header_read_timeout = Some(timeout);
+        self
+    }
+
+    pub fn keep_alive(mut self, keep_alive: bool) -> Self {
+        self.connection_config.keep_alive = keep_alive;
+        self
+    }
+
+    /// Closes the connection if it has no request in flight for `timeout`. This also applies to
+    /// freshly accepted connections that never send anything.
+    pub fn keep_alive_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_config.keep_alive_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Requests with more headers are answered with `431 Request Header Fields Too Large`.
+    ///
+    /// hyper itself never parses more than 100 headers, so larger values have no effect.
+    pub fn max_header_count(mut self, max_header_count: usize) -> Self {
+        self.connection_config.max_header_count = Some(max_header_count);
+        self
+    }
+
+    /// Requests whose header names and values are larger than `max_header_size` bytes in total
+    /// are answered with `431 Request Header Fields Too Large`.
+    ///
+    /// The connection's read buffer is limited to the same size, but never below 8192 bytes.
+    pub fn max_header_size(mut self, max_header_size: usize) -> Self {
+        self.connection_config.max_header_size = Some(max_header_size);
+        self
+    }
+
+    /// Stops accepting new connections while `max_connections` connections are open.
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// Keeps serving the connection after the client shut down its write side.
+    pub fn half_close(mut self, half_close: bool) -> Self {
+        self.connection_config.half_close = half_close;
+        self
+    }
+
+    pub fn tcp_nodelay(mut self, tcp_nodelay: bool) -> Self {
+        self.connection_config.tcp_nodelay = tcp_nodelay;
+        self
+    }
+
+    /// Called with every failure that ends a connection, [`log_connection_error`] by default.
+    pub fn connection_error_handler(
+        mut self,
+        connection_error_handler: impl Fn(&ConnectionError) + Send + Sync + 'static,
+    ) -> Self {
+        self.connection_config.connection_error_handler = Arc::new(connection_error_handler);
+        self
+    }
+
+    /// Creates the response sent when the request handler panics, an empty
+    /// `500 Internal Server Error` by default. The panic is reported to the connection error
+    /// handler, the connection stays open.
+    pub fn panic_response(
+        mut self,
+        panic_response: impl Fn() -> Response + Send + Sync + 'static,
+    ) -> Self {
+        self.connection_config.panic_response = Arc::new(panic_response);
+        self
+    }
+
+    /// See [`run_http1_tcp_server_with_graceful_shutdown`].
+    pub fn graceful_shutdown(
+        mut self,
+        app_loop_state_watcher: AppLoopStateWatcher,
+        drain_timeout: Duration,
+    ) -> Self {
+        self.graceful_shutdown = Some((app_loop_state_watcher, drain_timeout));
+        self
+    }
+
+    /// Terminates TLS on every accepted connection. The TLS handshake counts as idle time for
+    /// [`ServerBuilder::keep_alive_idle_timeout`].
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, tls_config: TlsConfig) -> Self {
+        self.tls_config = Some(tls_config);
+        self
+    }
+
+    /// Closes the connection if the TLS handshake did not complete within `timeout` after the
+    /// connection was accepted, [`DEFAULT_TLS_HANDSHAKE_TIMEOUT`] by default.
+    #[cfg(feature = "tls")]
+    pub fn tls_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_config.tls_handshake_timeout = timeout;
+        self
+    }
+
+    /// Binds a TCP listener to `listener_address` and serves it, see
+    /// [`ServerBuilder::serve_listener`].
+    pub async fn serve<
+        SocketAddressType: ToSocketAddrs,
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+        ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+        RequestHandlerFnType: RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+    >(
+        self,
+        listener_address: SocketAddressType,
+        request_handler: RequestHandlerFnType,
+        app_context: ApplicationContextType,
+    ) -> Result<ServerHandle, io::Error> {
+        let listener = TcpListener::bind(listener_address).await?;
+        self.serve_listener(listener, request_handler, app_context)
+    }
+
+    /// Serves the connections of an already bound listener, e.g. a [`TcpListener`], a
+    /// [`tokio::net::UnixListener`] or one of the [`inherited_listeners`].
+    ///
+    /// Connections that fail while being accepted are skipped, and accepting pauses briefly
+    /// while the process is out of file descriptors. Any other accept error stops the server,
+    /// the returned handle resolves with it.
+    pub fn serve_listener<
+        ListenerType: Listener,
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+        ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+        RequestHandlerFnType: RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+    >(
+        self,
+        listener: ListenerType,
+        request_handler: RequestHandlerFnType,
+        app_context: ApplicationContextType,
+    ) -> Result<ServerHandle, io::Error> {
+        let local_address = listener.local_address()?;
+        let server_address = local_address.clone();
+
+        #[cfg(feature = "tls")]
+        let tls_acceptor = match self.tls_config {
+            Some(tls_config) => Some(Arc::new(TlsAcceptor::new(
+                tls_config,
+                self.connection_config.protocol.alpn_protocols(),
+            )?)),
+            None => None,
+        };
+
+        let task = tokio::spawn(async move {
+            let request_handler = Arc::new(request_handler);
+            let application_context = Arc::new(app_context);
+            let connection_config = Arc::new(self.connection_config);
+            let connection_limit = self
+                .max_connections
+                .map(|max_connections| Arc::new(Semaphore::new(max_connections)));
+            let (app_loop_state_watcher, drain_timeout) = match self.graceful_shutdown {
+                Some((app_loop_state_watcher, drain_timeout)) => {
+                    (Some(app_loop_state_watcher), drain_timeout)
+                }
+                None => (None, Duration::ZERO),
+            };
+            let mut connections = JoinSet::new();
+            let mut next_connection_id = 0u64;
+
+            {
+                let accept = accept_connection(&listener, connection_limit.clone());
+                tokio::pin!(accept);
+
+                let reload_tls_config = async {
+                    #[cfg(feature = "tls")]
+                    if let Some(tls_acceptor) = &tls_acceptor {
+                        tls_acceptor.reload_on_change().await;
+                    }
+
+                    std::future::pending::<()>().await
+                };
+                tokio::pin!(reload_tls_config);
+
+                loop {
+                    tokio::select! {
+                        accepted = accept.as_mut() => {
+                            let (stream, peer_address, connection_permit) = accepted?;
+                            if connection_config.tcp_nodelay {
+                                if let Err(e) = stream.set_nodelay(true) {
+                                    log::warn!("Could not set TCP_NODELAY, error = {e}");
+                                }
+                            }
+
+                            let connection_info = ConnectionInfo {
+                                id: next_connection_id,
+                                peer_address,
+                                local_address: stream
+                                    .local_address()
+                                    .unwrap_or_else(|_| local_address.clone()),
+                                tls_info: None,
+                            };
+                            next_connection_id += 1;
+
+                            let connection = serve_accepted_connection(
+                                stream,
+                                connection_info.clone(),
+                                #[cfg(feature = "tls")]
+                                tls_acceptor.clone(),
+                                request_handler.clone(),
+                                application_context.clone(),
+                                connection_config.clone(),
+                                app_loop_state_watcher.clone(),
+                            );
+
+                            let connection_error_handler =
+                                connection_config.connection_error_handler.clone();
+                            connections.spawn(async move {
+                                if let Err(payload) = CatchUnwind::new(Box::pin(connection)).await {
+                                    connection_error_handler(&ConnectionError {
+                                        kind: ConnectionErrorKind::HandlerPanic,
+                                        connection_info,
+                                        error: panic_message(payload.as_ref()).into(),
+                                    });
+                                }
+                                drop(connection_permit);
+                            });
+
+                            accept.set(accept_connection(&listener, connection_limit.clone()));
+                        }
+                        Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                        _ = reload_tls_config.as_mut() => {}
+                        _ = wait_for_quit(app_loop_state_watcher.as_ref()) => {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            drop(listener);
+
+            let drain = async { while connections.join_next().await.is_some() {} };
+            if tokio::time::timeout(drain_timeout, drain).await.is_err() {
+                log::warn!(
+                    "Drain timeout elapsed, force-closing {} connection(s)",
+                    connections.len()
+                );
+                connections.shutdown().await;
+            }
+
+            Ok(())
+        });
+
+        Ok(ServerHandle {
+            local_address: server_address,
+            task,
+        })
+    }
+}
+
+/// Waits for a free connection slot first, so no connection is accepted above the limit.
+async fn accept_connection<ListenerType: Listener>(
+    listener: &ListenerType,
+    connection_limit: Option<Arc<Semaphore>>,
+) -> Result<
+    (
+        ListenerType::Stream,
+        SocketAddress,
+        Option<OwnedSemaphorePermit>,
+    ),
+    io::Error,
+> {
+    let connection_permit = match connection_limit {
+        Some(connection_limit) => Some(
+            connection_limit
+                .acquire_owned()
+                .await
+                .expect("connection limit semaphore is never closed"),
+        ),
+        None => None,
+    };
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer_address)) => return Ok((stream, peer_address, connection_permit)),
+            Err(e) if is_connection_error(&e) => {
+                log::debug!("Could not accept connection, error = {e}");
+            }
+            Err(e) if is_resource_error(&e) => {
+                log::warn!(
+                    "Could not accept connection, retrying in {ACCEPT_ERROR_BACKOFF:?}, error = {e}"
+                );
+                tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// The connection failed before it was accepted, the listener is fine.
+fn is_connection_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+    )
+}
+
+/// The process or the system ran out of file descriptors or memory for the moment.
+fn is_resource_error(e: &io::Error) -> bool {
+    #[cfg(unix)]
+    if let Some(libc::EMFILE | libc::ENFILE | libc::ENOBUFS | libc::ENOMEM) = e.raw_os_error() {
+        return true;
+    }
+
+    e.kind() == io::ErrorKind::OutOfMemory
+}
+
+async fn wait_for_quit(app_loop_state_watcher: Option<&AppLoopStateWatcher>) {
+    match app_loop_state_watcher {
+        Some(app_loop_state_watcher) => app_loop_state_watcher.wait_for_quit().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn serve_accepted_connection<
+    StreamType: ListenerStream,
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+    ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+    RequestHandlerFnType: RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+>(
+    stream: StreamType,
+    #[cfg_attr(not(feature = "tls"), allow(unused_mut))] mut connection_info: ConnectionInfo,
+    #[cfg(feature = "tls")] tls_acceptor: Option<Arc<TlsAcceptor>>,
+    request_handler: Arc<RequestHandlerFnType>,
+    application_context: Arc<ApplicationContextType>,
+    connection_config: Arc<ConnectionConfig>,
+    app_loop_state_watcher: Option<AppLoopStateWatcher>,
+) {
+    #[cfg(feature = "tls")]
+    if let Some(tls_acceptor) = tls_acceptor {
+        let idle_timeout = async {
+            match connection_config.keep_alive_idle_timeout {
+                Some(idle_timeout) => tokio::time::sleep(idle_timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let accepted = tokio::select! {
+            accepted = tls_acceptor.accept(stream) => accepted,
+            _ = wait_for_quit(app_loop_state_watcher.as_ref()) => return,
+            _ = idle_timeout => return,
+            _ = tokio::time::sleep(connection_config.tls_handshake_timeout) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "TLS handshake timed out",
+            )),
+        };
+
+        match accepted {
+            Ok((stream, tls_info)) => {
+                let protocol = match tls_info.alpn_protocol.as_deref() {
+                    Some(b"h2") => HttpProtocol::Http2,
+                    Some(b"http/1.1") => HttpProtocol::Http1,
+                    _ => connection_config.protocol,
+                };
+                connection_info.tls_info = Some(tls_info);
+
+                serve_connection(
+                    stream,
+                    protocol,
+                    connection_info,
+                    request_handler,
+                    application_context,
+                    connection_config,
+                    app_loop_state_watcher,
+                )
+                .await
+            }
+            Err(err) => {
+                let kind = match err.kind() {
+                    io::ErrorKind::TimedOut => ConnectionErrorKind::Timeout,
+                    _ => ConnectionErrorKind::Tls,
+                };
+                (connection_config.connection_error_handler)(&ConnectionError::from_io_error(
+                    kind,
+                    err,
+                    connection_info,
+                ))
+            }
+        }
+
+        return;
+    }
+
+    let protocol = connection_config.protocol;
+    serve_connection(
+        stream,
+        protocol,
+        connection_info,
+        request_handler,
+        application_context,
+        connection_config,
+        app_loop_state_watcher,
+    )
+    .await
+}
+
+async fn serve_connection<
+    IoType: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+    ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+    RequestHandlerFnType: RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+>(
+    io: IoType,
+    protocol: HttpProtocol,
+    connection_info: ConnectionInfo,
+    request_handler: Arc<RequestHandlerFnType>,
+    application_context: Arc<ApplicationContextType>,
+    connection_config: Arc<ConnectionConfig>,
+    app_loop_state_watcher: Option<AppLoopStateWatcher>,
+) {
+    let (requests_in_flight, requests_in_flight_watcher) = watch::channel(0usize);
+    let requests_in_flight = Arc::new(requests_in_flight);
+
+    let keep_alive_idle_timeout = || async {
+        match connection_config.keep_alive_idle_timeout {
+            Some(idle_timeout) => {
+                wait_for_idle(idle_timeout, requests_in_flight_watcher.clone()).await
+            }
+            None => std::future::pending().await,
+        }
+    };
+
+    let (is_http2, io) = match protocol {
+        HttpProtocol::Http1 => (false, Rewind::new(io)),
+        HttpProtocol::Http2 => (true, Rewind::new(io)),
+        HttpProtocol::Auto => {
+            let detected = tokio::select! {
+                detected = detect_http2_preface(io) => detected,
+                _ = wait_for_quit(app_loop_state_watcher.as_ref()) => return,
+                _ = keep_alive_idle_timeout() => return,
+            };
+
+            match detected {
+                Ok(detected) => detected,
+                Err(err) => {
+                    (connection_config.connection_error_handler)(&ConnectionError::from_io_error(
+                        ConnectionErrorKind::Other,
+                        err,
+                        connection_info,
+                    ));
+                    return;
+                }
+            }
+        }
+    };
+
+    let service = {
+        let connection_info = connection_info.clone();
+        let connection_config = connection_config.clone();
+        service_fn(move |req: Request| {
+            handle_request(
+                req,
+                connection_info.clone(),
+                request_handler.clone(),
+                application_context.clone(),
+                connection_config.clone(),
+                InFlightGuard::new(requests_in_flight.clone()),
+            )
+        })
+    };
+
+    let result = if is_http2 {
+        let (executor, _connection_scope) = ConnectionExecutor::new();
+        let mut builder = http2::Builder::new(executor);
+        if let Some(max_header_size) = connection_config.max_header_size {
+            builder.max_header_list_size(max_header_size.try_into().unwrap_or(u32::MAX));
+        }
+
+        drive_connection(
+            builder.serve_connection(io, service),
+            app_loop_state_watcher,
+            requests_in_flight_watcher.clone(),
+            keep_alive_idle_timeout(),
+        )
+        .await
+    } else {
+        let mut builder = http1::Builder::new();
+        builder
+            .keep_alive(connection_config.keep_alive)
+            .half_close(connection_config.half_close);
+        if let Some(header_read_timeout) = connection_config.header_read_timeout {
+            builder
+                .timer(TokioTimer)
+                .header_read_timeout(header_read_timeout);
+        }
+        if let Some(max_header_size) = connection_config.max_header_size {
+            builder.max_buf_size(max_header_size.max(MINIMUM_MAX_BUF_SIZE));
+        }
+
+        drive_connection(
+            builder.serve_connection(io, service),
+            app_loop_state_watcher,
+            requests_in_flight_watcher.clone(),
+            keep_alive_idle_timeout(),
+        )
+        .await
+    };
+
+    if let Err(err) = result {
+        (connection_config.connection_error_handler)(&ConnectionError::from_hyper_error(
+            err,
+            connection_info,
+        ));
+    }
+}
+
+/// The part of hyper's http1 and http2 connections that [`drive_connection`] needs.
+trait GracefulShutdown: Future<Output = Result<(), hyper::Error>> {
+    fn graceful_shutdown(self: Pin<&mut Self>);
+}
+
+impl<IoType, ServiceType> GracefulShutdown for http1::Connection<IoType, ServiceType>
+where
+    IoType: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    ServiceType: hyper::service::HttpService<hyper::body::Incoming, ResBody = ConnectionBody>,
+    ServiceType::Error: Into<Error>,
+{
+    fn graceful_shutdown(self: Pin<&mut Self>) {
+        http1::Connection::graceful_shutdown(self)
+    }
+}
+
+impl<IoType, ServiceType> GracefulShutdown
+    for http2::Connection<IoType, ServiceType, ConnectionExecutor>
+where
+    IoType: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    ServiceType: hyper::service::HttpService<hyper::body::Incoming, ResBody = ConnectionBody>,
+    ServiceType::Error: Into<Error>,
+    ConnectionExecutor: hyper::rt::bounds::Http2ConnExec<ServiceType::Future, ConnectionBody>,
+{
+    fn graceful_shutdown(self: Pin<&mut Self>) {
+        http2::Connection::graceful_shutdown(self)
+    }
+}
+
+async fn drive_connection(
+    connection: impl GracefulShutdown,
+    app_loop_state_watcher: Option<AppLoopStateWatcher>,
+    requests_in_flight_watcher: watch::Receiver<usize>,
+    keep_alive_idle_timeout: impl Future<Output = ()>,
+) -> Result<(), hyper::Error> {
+    tokio::pin!(connection);
+
+    // hyper only closes connections on graceful shutdown that already served a request, so
+    // connections without a request in flight are dropped here instead.
+    tokio::select! {
+        result = connection.as_mut() => result,
+        _ = wait_for_quit(app_loop_state_watcher.as_ref()) => {
+            connection.as_mut().graceful_shutdown();
+            tokio::select! {
+                result = connection.as_mut() => result,
+                _ = wait_for_idle(Duration::ZERO, requests_in_flight_watcher) => Ok(()),
+            }
+        }
+        _ = keep_alive_idle_timeout => Ok(()),
+    }
+}
+
+async fn handle_request<
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+    ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+    RequestHandlerFnType: RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+>(
+    mut req: Request,
+    connection_info: ConnectionInfo,
+    request_handler: Arc<RequestHandlerFnType>,
+    application_context: Arc<ApplicationContextType>,
+    connection_config: Arc<ConnectionConfig>,
+    in_flight_guard: InFlightGuard,
+) -> Result<hyper::Response<ConnectionBody>, Error> {
+    if let Some(tls_info) = &connection_info.tls_info {
+        req.extensions_mut().insert(tls_info.clone());
+    }
+    req.extensions_mut().insert(connection_info.clone());
+
+    let resp = match reject_request_headers(&connection_config, &req) {
+        Some(resp) => resp,
+        None => {
+            let request_line = format!("{} {}", req.method(), req.uri());
+            let request_handler_task = async move {
+                request_handler(
+                    req,
+                    application_context.clone(),
+                    RequestContextType::create(application_context),
+                )
+                .await
+            };
+
+            match service_helper(request_handler_task).await {
+                Ok(resp) => resp,
+                Err(payload) => {
+                    (connection_config.connection_error_handler)(&ConnectionError {
+                        kind: ConnectionErrorKind::HandlerPanic,
+                        connection_info,
+                        error: format!(
+                            "request handler panicked, request = {request_line}, panic = {}",
+                            panic_message(payload.as_ref())
+                        )
+                        .into(),
+                    });
+                    (connection_config.panic_response)()
+                }
+            }
+        }
+    };
+
+    Ok(resp.map(|body| ConnectionBody {
+        body,
+        _in_flight_guard: in_flight_guard,
+    }))
+}
+
+/// Resolves once no request has been in flight for `idle_timeout`.
+async fn wait_for_idle(
+    idle_timeout: Duration,
+    mut requests_in_flight_watcher: watch::Receiver<usize>,
+) {
+    loop {
+        if *requests_in_flight_watcher.borrow_and_update() == 0 {
+            tokio::select! {
+                _ = tokio::time::sleep(idle_timeout) => return,
+                _ = requests_in_flight_watcher.changed() => {}
+            }
+        } else if requests_in_flight_watcher.changed().await.is_err() {
+            return std::future::pending().await;
+        }
+    }
+}
+
+fn reject_request_headers(connection_config: &ConnectionConfig, req: &Request) -> Option<Response> {
+    let too_many_headers = connection_config
+        .max_header_count
+        .is_some_and(|max_header_count| req.headers().len() > max_header_count);
+    let too_large_headers = connection_config
+        .max_header_size
+        .is_some_and(|max_header_size| {
+            let header_size: usize = req
+                .headers()
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len())
+                .sum();
+            header_size > max_header_size
+        });
+
+    if too_many_headers || too_large_headers {
+        Some(create_empty_response(
+            hyper::StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+        ))
+    } else {
+        None
+    }
+}
+
+struct InFlightGuard(Arc<watch::Sender<usize>>);
+
+impl InFlightGuard {
+    fn new(requests_in_flight: Arc<watch::Sender<usize>>) -> Self {
+        requests_in_flight.send_modify(|requests_in_flight| *requests_in_flight += 1);
+        Self(requests_in_flight)
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0
+            .send_modify(|requests_in_flight| *requests_in_flight -= 1);
+    }
+}
+
+/// Keeps the request counted as in flight until hyper has written and dropped the response body.
+struct ConnectionBody {
+    body: ResponseBody,
+    _in_flight_guard: InFlightGuard,
+}
+
+impl hyper::body::Body for ConnectionBody {
+    type Data = hyper::body::Bytes;
+    type Error = Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<hyper::body::Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.body).poll_frame(cx)
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.body.size_hint()
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+}
+
+/// Resolves to the panic payload if the request handler panicked.
+async fn service_helper(
+    request_handler_task: impl Future<Output = Result<Response, ErrorResponse>>,
+) -> Result<Response, Box<dyn Any + Send>> {
+    match CatchUnwind::new(Box::pin(request_handler_task)).await? {
+        Ok(resp) => Ok(resp),
+        Err(resp) => Ok(resp.0),
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// An io object that replays the bytes already read from `inner` before reading from it again.
+pub(super) struct Rewind<IoType> {
+    prefix: Vec<u8>,
+    prefix_pos: usize,
+    inner: IoType,
+}
+
+impl<IoType> Rewind<IoType> {
+    pub(super) fn new(inner: IoType) -> Self {
+        Self::with_prefix(inner, Vec::new())
+    }
+
+    fn with_prefix(inner: IoType, prefix: Vec<u8>) -> Self {
+        Self {
+            prefix,
+            prefix_pos: 0,
+            inner,
+        }
+    }
+}
+
+/// Reads until the http2 connection preface either matched or diverged, and returns whether it
+/// matched together with an io object that still yields every byte read by the client.
+pub(super) async fn detect_http2_preface<IoType: AsyncRead + Unpin>(
+    mut io: IoType,
+) -> Result<(bool, Rewind<IoType>), io::Error> {
+    let mut prefix = vec![0u8; HTTP2_PREFACE.len()];
+    let mut read = 0;
+
+    while read < prefix.len() {
+        let size = io.read(&mut prefix[read..]).await?;
+        if size == 0 {
+            break;
+        }
+
+        read += size;
+        if prefix[..read] != HTTP2_PREFACE[..read] {
+            break;
+        }
+    }
+
+    prefix.truncate(read);
+    let is_http2 = prefix == HTTP2_PREFACE;
+
+    Ok((is_http2, Rewind::with_prefix(io, prefix)))
+}
+
+impl<IoType: AsyncRead + Unpin> AsyncRead for Rewind<IoType> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.prefix_pos < self.prefix.len() {
+            let size = buf.remaining().min(self.prefix.len() - self.prefix_pos);
+            buf.put_slice(&self.prefix[self.prefix_pos..self.prefix_pos + size]);
+            self.prefix_pos += size;
+            return Poll::Ready(Ok(()));
+        }
+
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<IoType: AsyncWrite + Unpin> AsyncWrite for Rewind<IoType> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<Result<usize, io::Error>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn http2_preface_detected() {
+        let mut payload = HTTP2_PREFACE.to_vec();
+        payload.extend_from_slice(b"frames");
+
+        let (is_http2, mut io) = detect_http2_preface(payload.as_slice()).await.unwrap();
+        assert!(is_http2);
+
+        let mut replayed = Vec::new();
+        io.read_to_end(&mut replayed).await.unwrap();
+        assert_eq!(replayed, payload);
+    }
+
+    #[tokio::test]
+    async fn http1_request_line_replayed() {
+        let payload = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec();
+
+        let (is_http2, mut io) = detect_http2_preface(payload.as_slice()).await.unwrap();
+        assert!(!is_http2);
+
+        let mut replayed = Vec::new();
+        io.read_to_end(&mut replayed).await.unwrap();
+        assert_eq!(replayed, payload);
+    }
+}
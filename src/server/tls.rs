@@ -0,0 +1,345 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use parking_lot::RwLock;
+use rustls::{
+    crypto::CryptoProvider,
+    pki_types::CertificateDer,
+    server::{ClientHello, ResolvesServerCert, VerifierBuilderError, WebPkiClientVerifier},
+    sign::CertifiedKey,
+    RootCertStore, ServerConfig,
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::server::TlsStream;
+
+use super::TlsInfo;
+
+#[derive(Debug)]
+pub enum TlsError {
+    Io(PathBuf, io::Error),
+    NoCertificate(PathBuf),
+    NoPrivateKey(PathBuf),
+    Rustls(rustls::Error),
+    ClientCertificateVerifier(VerifierBuilderError),
+}
+
+impl Display for TlsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsError::Io(path, e) => write!(f, "could not read {}: {e}", path.display()),
+            TlsError::NoCertificate(path) => {
+                write!(f, "no certificate found in {}", path.display())
+            }
+            TlsError::NoPrivateKey(path) => {
+                write!(f, "no private key found in {}", path.display())
+            }
+            TlsError::Rustls(e) => write!(f, "{e}"),
+            TlsError::ClientCertificateVerifier(e) => {
+                write!(f, "could not create client certificate verifier: {e:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TlsError {}
+
+impl From<rustls::Error> for TlsError {
+    fn from(e: rustls::Error) -> Self {
+        TlsError::Rustls(e)
+    }
+}
+
+impl From<VerifierBuilderError> for TlsError {
+    fn from(e: VerifierBuilderError) -> Self {
+        TlsError::ClientCertificateVerifier(e)
+    }
+}
+
+impl From<TlsError> for io::Error {
+    fn from(e: TlsError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+#[derive(Clone)]
+struct CertificateFiles {
+    cert_chain_path: PathBuf,
+    private_key_path: PathBuf,
+}
+
+/// Certificates and client authentication settings of an HTTPS server.
+///
+/// Every file is PEM encoded. The files are loaded when the server starts, and reloaded when
+/// any of them changes if a reload interval is set.
+#[derive(Clone)]
+pub struct TlsConfig {
+    default_certificate: CertificateFiles,
+    sni_certificates: Vec<(String, CertificateFiles)>,
+    client_ca_path: Option<PathBuf>,
+    client_certificate_required: bool,
+    reload_interval: Option<Duration>,
+}
+
+impl TlsConfig {
+    /// The certificate sent when the client did not ask for a server name with a dedicated
+    /// certificate.
+    pub fn new(cert_chain_path: impl Into<PathBuf>, private_key_path: impl Into<PathBuf>) -> Self {
+        Self {
+            default_certificate: CertificateFiles {
+                cert_chain_path: cert_chain_path.into(),
+                private_key_path: private_key_path.into(),
+            },
+            sni_certificates: Vec::new(),
+            client_ca_path: None,
+            client_certificate_required: false,
+            reload_interval: None,
+        }
+    }
+
+    /// The certificate sent when the client asks for `server_name` through SNI.
+    pub fn sni_certificate(
+        mut self,
+        server_name: impl ToString,
+        cert_chain_path: impl Into<PathBuf>,
+        private_key_path: impl Into<PathBuf>,
+    ) -> Self {
+        self.sni_certificates.push((
+            server_name.to_string().to_lowercase(),
+            CertificateFiles {
+                cert_chain_path: cert_chain_path.into(),
+                private_key_path: private_key_path.into(),
+            },
+        ));
+        self
+    }
+
+    /// Verifies client certificates against the CA certificates in `client_ca_path`. If
+    /// `required` is false, clients without a certificate are accepted too.
+    ///
+    /// The verified certificate chain is available through the [`TlsInfo`] request extension.
+    pub fn client_certificate_verification(
+        mut self,
+        client_ca_path: impl Into<PathBuf>,
+        required: bool,
+    ) -> Self {
+        self.client_ca_path = Some(client_ca_path.into());
+        self.client_certificate_required = required;
+        self
+    }
+
+    /// Checks the files for modifications every `reload_interval`. Existing connections keep
+    /// using the configuration they were accepted with.
+    pub fn reload_interval(mut self, reload_interval: Duration) -> Self {
+        self.reload_interval = Some(reload_interval);
+        self
+    }
+
+    fn paths(&self) -> impl Iterator<Item = &Path> {
+        std::iter::once(&self.default_certificate)
+            .chain(self.sni_certificates.iter().map(|(_, files)| files))
+            .flat_map(|files| {
+                [
+                    files.cert_chain_path.as_path(),
+                    files.private_key_path.as_path(),
+                ]
+            })
+            .chain(self.client_ca_path.as_deref())
+    }
+
+    fn modification_times(&self) -> Vec<Option<SystemTime>> {
+        self.paths()
+            .map(|path| {
+                std::fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+            })
+            .collect()
+    }
+
+    fn load(&self, alpn_protocols: &[Vec<u8>]) -> Result<ServerConfig, TlsError> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+        let resolver = CertificateResolver {
+            default_certificate: load_certified_key(&self.default_certificate, &provider)?,
+            sni_certificates: self
+                .sni_certificates
+                .iter()
+                .map(|(server_name, files)| {
+                    Ok((server_name.clone(), load_certified_key(files, &provider)?))
+                })
+                .collect::<Result<_, TlsError>>()?,
+        };
+
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let builder = match &self.client_ca_path {
+            Some(client_ca_path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in load_certificates(client_ca_path)? {
+                    roots.add(cert)?;
+                }
+
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+                let verifier = if self.client_certificate_required {
+                    verifier
+                } else {
+                    verifier.allow_unauthenticated()
+                };
+
+                builder.with_client_cert_verifier(verifier.build()?)
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        let mut server_config = builder.with_cert_resolver(Arc::new(resolver));
+        server_config.alpn_protocols = alpn_protocols.to_vec();
+
+        Ok(server_config)
+    }
+}
+
+fn load_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let file = File::open(path).map_err(|e| TlsError::Io(path.into(), e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| TlsError::Io(path.into(), e))?;
+
+    if certs.is_empty() {
+        Err(TlsError::NoCertificate(path.into()))
+    } else {
+        Ok(certs)
+    }
+}
+
+fn load_certified_key(
+    files: &CertificateFiles,
+    provider: &CryptoProvider,
+) -> Result<Arc<CertifiedKey>, TlsError> {
+    let cert_chain = load_certificates(&files.cert_chain_path)?;
+
+    let path = &files.private_key_path;
+    let file = File::open(path).map_err(|e| TlsError::Io(path.into(), e))?;
+    let private_key = rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| TlsError::Io(path.into(), e))?
+        .ok_or_else(|| TlsError::NoPrivateKey(path.into()))?;
+
+    Ok(Arc::new(CertifiedKey::from_der(
+        cert_chain,
+        private_key,
+        provider,
+    )?))
+}
+
+#[derive(Debug)]
+struct CertificateResolver {
+    default_certificate: Arc<CertifiedKey>,
+    sni_certificates: HashMap<String, Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let certificate = client_hello
+            .server_name()
+            .and_then(|server_name| self.sni_certificates.get(&server_name.to_lowercase()))
+            .unwrap_or(&self.default_certificate);
+
+        Some(certificate.clone())
+    }
+}
+
+pub(super) struct TlsAcceptor {
+    tls_config: Arc<TlsConfig>,
+    alpn_protocols: Arc<[Vec<u8>]>,
+    acceptor: RwLock<tokio_rustls::TlsAcceptor>,
+}
+
+impl TlsAcceptor {
+    pub(super) fn new(
+        tls_config: TlsConfig,
+        alpn_protocols: Vec<Vec<u8>>,
+    ) -> Result<Self, TlsError> {
+        let server_config = tls_config.load(&alpn_protocols)?;
+
+        Ok(Self {
+            tls_config: Arc::new(tls_config),
+            alpn_protocols: alpn_protocols.into(),
+            acceptor: RwLock::new(Arc::new(server_config).into()),
+        })
+    }
+
+    pub(super) async fn accept<IoType: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        io: IoType,
+    ) -> Result<(TlsStream<IoType>, TlsInfo), io::Error> {
+        let acceptor = self.acceptor.read().clone();
+        let stream = acceptor.accept(io).await?;
+
+        let connection = stream.get_ref().1;
+        let tls_info = TlsInfo {
+            server_name: connection.server_name().map(ToString::to_string),
+            alpn_protocol: connection.alpn_protocol().map(Vec::from),
+            peer_certificates: connection
+                .peer_certificates()
+                .map(|certs| certs.iter().map(|cert| cert.to_vec()).collect())
+                .unwrap_or_default(),
+        };
+
+        Ok((stream, tls_info))
+    }
+
+    /// Never resolves, reloads the configuration whenever one of its files changed. The files
+    /// are read on the blocking thread pool.
+    pub(super) async fn reload_on_change(&self) {
+        let reload_interval = match self.tls_config.reload_interval {
+            Some(reload_interval) => reload_interval,
+            None => return std::future::pending().await,
+        };
+
+        let mut modification_times = self.modification_times().await;
+        loop {
+            tokio::time::sleep(reload_interval).await;
+
+            let current_modification_times = self.modification_times().await;
+            if current_modification_times == modification_times {
+                continue;
+            }
+
+            // A failed reload is retried on the next tick, e.g. when a key was read while it
+            // was being rewritten.
+            let tls_config = self.tls_config.clone();
+            let alpn_protocols = self.alpn_protocols.clone();
+            match tokio::task::spawn_blocking(move || tls_config.load(&alpn_protocols)).await {
+                Ok(Ok(server_config)) => {
+                    *self.acceptor.write() = Arc::new(server_config).into();
+                    modification_times = current_modification_times;
+                    log::info!("TLS configuration reloaded");
+                }
+                Ok(Err(e)) => {
+                    log::error!(
+                        "Could not reload TLS configuration, keeping the previous one, error = {e}"
+                    );
+                }
+                Err(e) => {
+                    log::error!(
+                        "Could not reload TLS configuration, keeping the previous one, error = {e}"
+                    );
+                }
+            }
+        }
+    }
+
+    async fn modification_times(&self) -> Vec<Option<SystemTime>> {
+        let tls_config = self.tls_config.clone();
+        tokio::task::spawn_blocking(move || tls_config.modification_times())
+            .await
+            .unwrap_or_default()
+    }
+}
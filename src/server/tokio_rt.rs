@@ -0,0 +1,98 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::watch;
+
+/// Spawns the stream tasks of an HTTP/2 connection, they are cancelled once the
+/// [`ConnectionScope`] of the connection is dropped, e.g. when the connection task is aborted
+/// after the drain timeout.
+#[derive(Clone)]
+pub(super) struct ConnectionExecutor {
+    cancelled: watch::Receiver<()>,
+}
+
+/// Owned by the connection future, dropping it cancels the tasks of its [`ConnectionExecutor`].
+pub(super) struct ConnectionScope {
+    _cancel: watch::Sender<()>,
+}
+
+impl ConnectionExecutor {
+    pub(super) fn new() -> (Self, ConnectionScope) {
+        let (cancel, cancelled) = watch::channel(());
+        (Self { cancelled }, ConnectionScope { _cancel: cancel })
+    }
+}
+
+impl<FutureType> hyper::rt::Executor<FutureType> for ConnectionExecutor
+where
+    FutureType: Future + Send + 'static,
+    FutureType::Output: Send + 'static,
+{
+    fn execute(&self, fut: FutureType) {
+        let mut cancelled = self.cancelled.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = fut => {}
+                // Only fails once the sender of the scope is dropped, nothing is ever sent.
+                _ = cancelled.changed() => {}
+            }
+        });
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(super) struct TokioTimer;
+
+struct TokioSleep(Pin<Box<tokio::time::Sleep>>);
+
+impl hyper::rt::Timer for TokioTimer {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn hyper::rt::Sleep>> {
+        Box::pin(TokioSleep(Box::pin(tokio::time::sleep(duration))))
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn hyper::rt::Sleep>> {
+        Box::pin(TokioSleep(Box::pin(tokio::time::sleep_until(
+            deadline.into(),
+        ))))
+    }
+}
+
+impl Future for TokioSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.as_mut().poll(cx)
+    }
+}
+
+impl hyper::rt::Sleep for TokioSleep {}
+
+#[cfg(test)]
+mod test {
+    use hyper::rt::Executor;
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn dropped_scope_cancels_tasks() {
+        let (executor, connection_scope) = ConnectionExecutor::new();
+        let (sender, receiver) = oneshot::channel::<()>();
+        executor.execute(async move {
+            let _sender = sender;
+            std::future::pending::<()>().await
+        });
+
+        drop(connection_scope);
+
+        // The sender is dropped together with the cancelled task.
+        assert!(tokio::time::timeout(Duration::from_secs(1), receiver)
+            .await
+            .unwrap()
+            .is_err());
+    }
+}
@@ -0,0 +1,284 @@
+use std::{future::Future, marker::PhantomData, sync::Arc};
+
+use hyper::{
+    header::{HeaderName, HeaderValue, COOKIE, HOST},
+    server::conn::http1,
+    service::service_fn,
+    Method,
+};
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::{
+    application_context_trait::ApplicationContextTrait,
+    error::Error,
+    request_context_trait::RequestContextTrait,
+    request_handler::{ErrorResponse, Request, RequestHandlerFn, Response},
+    response_body::{AsyncStream, ResponseBody},
+};
+
+/// Size of the in-memory pipe between the client and the handler.
+const PIPE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Calls a request handler in-process, without binding a socket.
+///
+/// Requests are sent through an in-memory HTTP/1.1 connection, so the handler receives a real
+/// [`Request`] with an `Incoming` body. The handler's [`Response`] is returned as it is, the
+/// server level settings of [`crate::server::ServerBuilder`] do not apply and the request
+/// extensions added by the server, e.g. [`crate::server::ConnectionInfo`], are missing.
+pub struct TestClient<
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+    ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+    RequestHandlerFnType: RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+> {
+    request_handler: Arc<RequestHandlerFnType>,
+    app_context: Arc<ApplicationContextType>,
+    _phantom: PhantomData<fn() -> (RequestContextType, ReturnType)>,
+}
+
+impl<
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+        ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+        RequestHandlerFnType: RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+    > TestClient<ApplicationContextType, RequestContextType, ReturnType, RequestHandlerFnType>
+{
+    pub fn new(request_handler: RequestHandlerFnType, app_context: ApplicationContextType) -> Self {
+        Self {
+            request_handler: Arc::new(request_handler),
+            app_context: Arc::new(app_context),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn app_context(&self) -> &Arc<ApplicationContextType> {
+        &self.app_context
+    }
+
+    pub fn request(
+        &self,
+        method: Method,
+        uri: &str,
+    ) -> TestRequestBuilder<
+        '_,
+        ApplicationContextType,
+        RequestContextType,
+        ReturnType,
+        RequestHandlerFnType,
+    > {
+        TestRequestBuilder {
+            client: self,
+            request: hyper::Request::builder().method(method).uri(uri),
+            cookies: Vec::new(),
+            body: ResponseBody::None,
+        }
+    }
+
+    pub fn get(
+        &self,
+        uri: &str,
+    ) -> TestRequestBuilder<
+        '_,
+        ApplicationContextType,
+        RequestContextType,
+        ReturnType,
+        RequestHandlerFnType,
+    > {
+        self.request(Method::GET, uri)
+    }
+
+    pub fn post(
+        &self,
+        uri: &str,
+    ) -> TestRequestBuilder<
+        '_,
+        ApplicationContextType,
+        RequestContextType,
+        ReturnType,
+        RequestHandlerFnType,
+    > {
+        self.request(Method::POST, uri)
+    }
+
+    pub fn put(
+        &self,
+        uri: &str,
+    ) -> TestRequestBuilder<
+        '_,
+        ApplicationContextType,
+        RequestContextType,
+        ReturnType,
+        RequestHandlerFnType,
+    > {
+        self.request(Method::PUT, uri)
+    }
+
+    pub fn delete(
+        &self,
+        uri: &str,
+    ) -> TestRequestBuilder<
+        '_,
+        ApplicationContextType,
+        RequestContextType,
+        ReturnType,
+        RequestHandlerFnType,
+    > {
+        self.request(Method::DELETE, uri)
+    }
+}
+
+pub struct TestRequestBuilder<
+    'a,
+    ApplicationContextType: ApplicationContextTrait,
+    RequestContextType: RequestContextTrait<ApplicationContextType>,
+    ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+    RequestHandlerFnType: RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+> {
+    client: &'a TestClient<
+        ApplicationContextType,
+        RequestContextType,
+        ReturnType,
+        RequestHandlerFnType,
+    >,
+    request: hyper::http::request::Builder,
+    cookies: Vec<String>,
+    body: ResponseBody,
+}
+
+impl<
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+        ReturnType: Future<Output = Result<Response, ErrorResponse>> + Send + Sync + 'static,
+        RequestHandlerFnType: RequestHandlerFn<ApplicationContextType, RequestContextType, ReturnType>,
+    >
+    TestRequestBuilder<
+        '_,
+        ApplicationContextType,
+        RequestContextType,
+        ReturnType,
+        RequestHandlerFnType,
+    >
+{
+    /// Invalid names or values make [`TestRequestBuilder::send`] fail.
+    pub fn header<NameType, ValueType>(mut self, name: NameType, value: ValueType) -> Self
+    where
+        HeaderName: TryFrom<NameType>,
+        <HeaderName as TryFrom<NameType>>::Error: Into<hyper::http::Error>,
+        HeaderValue: TryFrom<ValueType>,
+        <HeaderValue as TryFrom<ValueType>>::Error: Into<hyper::http::Error>,
+    {
+        self.request = self.request.header(name, value);
+        self
+    }
+
+    /// All cookies are sent in a single `Cookie` header.
+    pub fn cookie(mut self, name: &str, value: &str) -> Self {
+        self.cookies
+            .push(cookie::Cookie::new(name, value).encoded().to_string());
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = ResponseBody::from(body.into());
+        self
+    }
+
+    /// The body is sent chunked, one chunk per stream item.
+    pub fn body_stream(mut self, stream: impl AsyncStream<Vec<u8>>) -> Self {
+        self.body = ResponseBody::from(stream);
+        self
+    }
+
+    pub async fn send(self) -> Result<Response, Error> {
+        let mut request = self.request;
+        if !self.cookies.is_empty() {
+            request = request.header(COOKIE, self.cookies.join("; "));
+        }
+        if request
+            .headers_ref()
+            .is_some_and(|headers| !headers.contains_key(HOST))
+        {
+            request = request.header(HOST, "localhost");
+        }
+        let request = request.body(self.body)?;
+
+        let mut connection = TestConnection::open(request).await?;
+        let req = connection.received_request().await?;
+
+        let app_context = self.client.app_context.clone();
+        let mut resp = match (self.client.request_handler)(
+            req,
+            app_context.clone(),
+            RequestContextType::create(app_context),
+        )
+        .await
+        {
+            Ok(resp) => resp,
+            Err(resp) => resp.0,
+        };
+
+        // the request body may still be read while the response body is streamed
+        resp.extensions_mut().insert(Arc::new(connection));
+
+        Ok(resp)
+    }
+}
+
+/// Both ends of the in-memory connection, closed when dropped.
+struct TestConnection {
+    request_receiver: mpsc::UnboundedReceiver<Request>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl TestConnection {
+    async fn open(request: hyper::Request<ResponseBody>) -> Result<Self, Error> {
+        let (client_io, server_io) = tokio::io::duplex(PIPE_BUFFER_SIZE);
+        let (request_sender, request_receiver) = mpsc::unbounded_channel();
+
+        let server_task = tokio::spawn(async move {
+            let service =
+                service_fn(move |req: Request| capture_request(req, request_sender.clone()));
+            let _ = http1::Builder::new()
+                .serve_connection(server_io, service)
+                .await;
+        });
+
+        let (mut request_sender, client_connection) =
+            hyper::client::conn::http1::handshake(client_io).await?;
+        let client_task = tokio::spawn(async move {
+            let _ = client_connection.await;
+        });
+        let request_task = tokio::spawn(async move {
+            let _ = request_sender.send_request(request).await;
+        });
+
+        Ok(Self {
+            request_receiver,
+            tasks: vec![server_task, client_task, request_task],
+        })
+    }
+
+    async fn received_request(&mut self) -> Result<Request, Error> {
+        self.request_receiver
+            .recv()
+            .await
+            .ok_or_else(|| "the test request could not be sent".into())
+    }
+}
+
+/// Never answers, the request is answered by calling the handler directly.
+async fn capture_request(
+    req: Request,
+    request_sender: mpsc::UnboundedSender<Request>,
+) -> Result<hyper::Response<ResponseBody>, Error> {
+    let _ = request_sender.send(req);
+    std::future::pending().await
+}
+
+impl Drop for TestConnection {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
@@ -1,20 +1,42 @@
-use std::sync::{atomic::AtomicBool, Arc};
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
 
 use crate::{
+    app_loop_state::AppLoopState,
     application_context_trait::ApplicationContextTrait,
+    content_type::ContentType,
     create_request_handler_call_chain,
+    decorators::{catch_panic, CatchPanicApplicationContext},
     request_context_trait::RequestContextTrait,
     request_handler::{
         ErrorResponse, Request, RequestHandlerFn, RequestHandlerReturnTrait, Response,
     },
+    response::create_static_str_response,
     response_body::ResponseBody,
-    server::run_http1_tcp_server,
+    server::{
+        run_auto_http_tcp_server, run_http1_tcp_server,
+        run_http1_tcp_server_with_graceful_shutdown, run_http2_tcp_server, AcceptFuture,
+        ConnectionErrorKind, ConnectionInfo, Listener, ServerBuilder, SocketAddress,
+    },
+    test_client::TestClient,
 };
 
 struct TestApplicationContext;
 
 impl ApplicationContextTrait for TestApplicationContext {}
 
+impl CatchPanicApplicationContext for TestApplicationContext {
+    fn panic_response(&self) -> Response {
+        create_static_str_response(
+            hyper::StatusCode::INTERNAL_SERVER_ERROR,
+            "caught by catch_panic",
+            ContentType::TextPlain,
+        )
+    }
+}
+
 struct TestRequestContext {
     _app_context: Arc<TestApplicationContext>,
     middleware_called: Arc<AtomicBool>,
@@ -37,6 +59,15 @@ async fn test_request_handler(
     Ok(Response::new("test_request_handler".into()))
 }
 
+async fn test_slow_request_handler(
+    _req: Request,
+    _app_context: Arc<TestApplicationContext>,
+    _request_context: TestRequestContext,
+) -> Result<Response, ErrorResponse> {
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    Ok(Response::new("test_slow_request_handler".into()))
+}
+
 trait TestMiddlewareTrait {
     fn set_called(&mut self);
 }
@@ -72,10 +103,9 @@ async fn test_middleware<
 }
 
 #[tokio::test]
-#[serial_test::serial]
 async fn aborting_server() {
     let server_task = run_http1_tcp_server(
-        ("127.0.0.1", 30000),
+        ("127.0.0.1", 0),
         test_request_handler,
         TestApplicationContext,
     )
@@ -86,18 +116,18 @@ async fn aborting_server() {
 }
 
 #[tokio::test]
-#[serial_test::serial]
 async fn request_handler_called() {
     let server_task = run_http1_tcp_server(
-        ("127.0.0.1", 30000),
+        ("127.0.0.1", 0),
         test_request_handler,
         TestApplicationContext,
     )
     .await
     .unwrap();
+    let url = format!("http://{}", server_task.local_address());
 
     assert_eq!(
-        reqwest::get("http://localhost:30000")
+        reqwest::get(&url)
             .await
             .unwrap()
             .text()
@@ -111,26 +141,2245 @@ async fn request_handler_called() {
 }
 
 #[tokio::test]
-#[serial_test::serial]
 async fn middleware_called() {
     let server_task = run_http1_tcp_server(
-        ("127.0.0.1", 30000),
+        ("127.0.0.1", 0),
         create_request_handler_call_chain!(test_middleware, test_request_handler),
         TestApplicationContext,
     )
     .await
     .unwrap();
+    let url = format!("http://{}", server_task.local_address());
+
+    assert_eq!(
+        reqwest::get(&url)
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap()
+            .as_str(),
+        "test_middleware.test_request_handler"
+    );
+
+    server_task.abort();
+}
+
+#[tokio::test]
+async fn graceful_shutdown_drains_in_flight_request() {
+    let app_loop_state = AppLoopState::new();
+    let server_task = run_http1_tcp_server_with_graceful_shutdown(
+        ("127.0.0.1", 0),
+        test_slow_request_handler,
+        TestApplicationContext,
+        app_loop_state.watcher(),
+        Duration::from_secs(5),
+    )
+    .await
+    .unwrap();
+    let url = format!("http://{}", server_task.local_address());
+
+    let client = reqwest::Client::new();
+    let request_task = tokio::spawn(client.get(&url).send());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    app_loop_state.stop_loop();
 
     assert_eq!(
-        reqwest::get("http://localhost:30000")
+        request_task
             .await
             .unwrap()
+            .unwrap()
             .text()
             .await
             .unwrap()
             .as_str(),
+        "test_slow_request_handler"
+    );
+
+    tokio::time::timeout(Duration::from_secs(1), server_task)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+    assert!(reqwest::get(&url).await.is_err());
+}
+
+#[tokio::test]
+async fn graceful_shutdown_force_closes_after_drain_timeout() {
+    let app_loop_state = AppLoopState::new();
+    let server_task = run_http1_tcp_server_with_graceful_shutdown(
+        ("127.0.0.1", 0),
+        test_slow_request_handler,
+        TestApplicationContext,
+        app_loop_state.watcher(),
+        Duration::from_millis(100),
+    )
+    .await
+    .unwrap();
+    let url = format!("http://{}", server_task.local_address());
+
+    let client = reqwest::Client::new();
+    let request_task = tokio::spawn(client.get(&url).send());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    app_loop_state.stop_loop();
+
+    tokio::time::timeout(Duration::from_millis(300), server_task)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+    assert!(request_task.await.unwrap().is_err());
+}
+
+#[tokio::test]
+async fn keep_alive_idle_timeout_closes_silent_connection() {
+    use tokio::io::AsyncReadExt;
+
+    let server_task = ServerBuilder::new()
+        .keep_alive_idle_timeout(Duration::from_millis(100))
+        .serve(
+            ("127.0.0.1", 0),
+            test_request_handler,
+            TestApplicationContext,
+        )
+        .await
+        .unwrap();
+
+    let mut stream = tokio::net::TcpStream::connect(server_task.local_address().to_string())
+        .await
+        .unwrap();
+    let mut buf = [0u8; 16];
+    let read = tokio::time::timeout(Duration::from_secs(1), stream.read(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(read, 0);
+
+    server_task.abort();
+}
+
+#[tokio::test]
+async fn max_header_count_exceeded() {
+    let server_task = ServerBuilder::new()
+        .max_header_count(4)
+        .serve(
+            ("127.0.0.1", 0),
+            test_request_handler,
+            TestApplicationContext,
+        )
+        .await
+        .unwrap();
+    let url = format!("http://{}", server_task.local_address());
+
+    let client = reqwest::Client::new();
+    let mut request = client.get(&url);
+    for i in 0..8 {
+        request = request.header(format!("x-header-{i}"), "value");
+    }
+
+    assert_eq!(
+        request.send().await.unwrap().status(),
+        reqwest::StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+    );
+
+    server_task.abort();
+}
+
+#[tokio::test]
+async fn http2_prior_knowledge() {
+    let server_task = run_http2_tcp_server(
+        ("127.0.0.1", 0),
+        create_request_handler_call_chain!(test_middleware, test_request_handler),
+        TestApplicationContext,
+    )
+    .await
+    .unwrap();
+    let url = format!("http://{}", server_task.local_address());
+
+    let client = reqwest::Client::builder()
+        .http2_prior_knowledge()
+        .build()
+        .unwrap();
+    let response = client.get(&url).send().await.unwrap();
+
+    assert_eq!(response.version(), reqwest::Version::HTTP_2);
+    assert_eq!(
+        response.text().await.unwrap().as_str(),
         "test_middleware.test_request_handler"
     );
 
     server_task.abort();
 }
+
+#[tokio::test]
+async fn auto_http_protocol_detection() {
+    let server_task = run_auto_http_tcp_server(
+        ("127.0.0.1", 0),
+        test_request_handler,
+        TestApplicationContext,
+    )
+    .await
+    .unwrap();
+    let url = format!("http://{}", server_task.local_address());
+
+    let http1_response = reqwest::Client::builder()
+        .http1_only()
+        .build()
+        .unwrap()
+        .get(&url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(http1_response.version(), reqwest::Version::HTTP_11);
+    assert_eq!(
+        http1_response.text().await.unwrap().as_str(),
+        "test_request_handler"
+    );
+
+    let http2_response = reqwest::Client::builder()
+        .http2_prior_knowledge()
+        .build()
+        .unwrap()
+        .get(&url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(http2_response.version(), reqwest::Version::HTTP_2);
+    assert_eq!(
+        http2_response.text().await.unwrap().as_str(),
+        "test_request_handler"
+    );
+
+    server_task.abort();
+}
+
+async fn connection_info_handler(
+    req: Request,
+    _app_context: Arc<TestApplicationContext>,
+    _request_context: TestRequestContext,
+) -> Result<Response, ErrorResponse> {
+    let connection_info = req.extensions().get::<ConnectionInfo>().unwrap();
+    Ok(Response::new(
+        format!(
+            "{} {} {}",
+            connection_info.id, connection_info.peer_address, connection_info.local_address
+        )
+        .into(),
+    ))
+}
+
+#[tokio::test]
+async fn connection_info_request_extension() {
+    let server_task = run_http1_tcp_server(
+        ("127.0.0.1", 0),
+        connection_info_handler,
+        TestApplicationContext,
+    )
+    .await
+    .unwrap();
+    let url = format!("http://{}", server_task.local_address());
+
+    for expected_id in 0..2 {
+        let client = reqwest::Client::new();
+        let response = client.get(&url).send().await.unwrap();
+        let text = response.text().await.unwrap();
+        let mut fields = text.split(' ');
+
+        assert_eq!(fields.next().unwrap(), expected_id.to_string());
+        assert!(fields.next().unwrap().starts_with("127.0.0.1:"));
+        assert_eq!(
+            fields.next().unwrap(),
+            server_task.local_address().to_string()
+        );
+    }
+
+    server_task.abort();
+}
+
+async fn test_panicking_request_handler(
+    _req: Request,
+    _app_context: Arc<TestApplicationContext>,
+    _request_context: TestRequestContext,
+) -> Result<Response, ErrorResponse> {
+    panic!("test_panicking_request_handler");
+}
+
+#[tokio::test]
+async fn connection_error_handler_called() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (error_sender, mut error_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let server_task = ServerBuilder::new()
+        .connection_error_handler(move |connection_error| {
+            error_sender
+                .send((
+                    connection_error.kind,
+                    connection_error.connection_info.peer_address.clone(),
+                ))
+                .unwrap();
+        })
+        .serve(
+            ("127.0.0.1", 0),
+            test_panicking_request_handler,
+            TestApplicationContext,
+        )
+        .await
+        .unwrap();
+
+    let mut stream = tokio::net::TcpStream::connect(server_task.local_address().to_string())
+        .await
+        .unwrap();
+    stream.write_all(b"NOT HTTP\r\n\r\n").await.unwrap();
+    stream.read_to_end(&mut Vec::new()).await.unwrap();
+    let (kind, peer_address) = error_receiver.recv().await.unwrap();
+    assert_eq!(kind, ConnectionErrorKind::Parse);
+    assert_eq!(
+        peer_address,
+        SocketAddress::Tcp(stream.local_addr().unwrap())
+    );
+
+    let url = format!("http://{}", server_task.local_address());
+    assert_eq!(
+        reqwest::get(&url).await.unwrap().status(),
+        reqwest::StatusCode::INTERNAL_SERVER_ERROR
+    );
+    let (kind, _) = error_receiver.recv().await.unwrap();
+    assert_eq!(kind, ConnectionErrorKind::HandlerPanic);
+
+    server_task.abort();
+}
+
+#[tokio::test]
+async fn handler_panic_keeps_connection_usable() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let server_task = ServerBuilder::new()
+        .panic_response(|| {
+            create_static_str_response(
+                hyper::StatusCode::INTERNAL_SERVER_ERROR,
+                "panicked",
+                ContentType::TextPlain,
+            )
+        })
+        .serve(
+            ("127.0.0.1", 0),
+            test_panicking_request_handler,
+            TestApplicationContext,
+        )
+        .await
+        .unwrap();
+
+    let mut stream = tokio::net::TcpStream::connect(server_task.local_address().to_string())
+        .await
+        .unwrap();
+    stream
+        .write_all(
+            b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n\
+              GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert_eq!(response.matches("HTTP/1.1 500").count(), 2);
+    assert_eq!(response.matches("panicked").count(), 2);
+
+    server_task.abort();
+}
+
+#[tokio::test]
+async fn catch_panic_decorator() {
+    let client = TestClient::new(
+        create_request_handler_call_chain!(catch_panic, test_panicking_request_handler),
+        TestApplicationContext,
+    );
+
+    let mut response = client.get("/").send().await.unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        response.body_mut().read_to_string().await.unwrap(),
+        "caught by catch_panic"
+    );
+}
+
+#[tokio::test]
+async fn serve_pre_bound_tcp_listener() {
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+        .await
+        .unwrap();
+    let listener_address = listener.local_addr().unwrap();
+
+    let server_task = ServerBuilder::new()
+        .serve_listener(listener, test_request_handler, TestApplicationContext)
+        .unwrap();
+    assert_eq!(
+        server_task.local_address(),
+        &SocketAddress::Tcp(listener_address)
+    );
+
+    let response = reqwest::get(format!("http://{listener_address}"))
+        .await
+        .unwrap();
+    assert_eq!(
+        response.text().await.unwrap().as_str(),
+        "test_request_handler"
+    );
+
+    server_task.abort();
+}
+
+/// Fails the first accepts with `errors`, last one first, then accepts from `listener`.
+struct FailingListener {
+    listener: tokio::net::TcpListener,
+    errors: parking_lot::Mutex<Vec<std::io::Error>>,
+}
+
+impl Listener for FailingListener {
+    type Stream = tokio::net::TcpStream;
+
+    fn accept(&self) -> AcceptFuture<'_, Self::Stream> {
+        match self.errors.lock().pop() {
+            Some(e) => Box::pin(std::future::ready(Err(e))),
+            None => Listener::accept(&self.listener),
+        }
+    }
+
+    fn local_address(&self) -> Result<SocketAddress, std::io::Error> {
+        self.listener.local_address()
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn temporary_accept_errors_keep_serving() {
+    let listener = FailingListener {
+        listener: tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap(),
+        errors: parking_lot::Mutex::new(vec![
+            std::io::Error::from_raw_os_error(libc::EMFILE),
+            std::io::ErrorKind::ConnectionAborted.into(),
+        ]),
+    };
+
+    let server_task = ServerBuilder::new()
+        .serve_listener(listener, test_request_handler, TestApplicationContext)
+        .unwrap();
+    let url = format!("http://{}", server_task.local_address());
+
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(
+        response.text().await.unwrap().as_str(),
+        "test_request_handler"
+    );
+    assert!(!server_task.is_finished());
+
+    server_task.abort();
+}
+
+#[tokio::test]
+async fn fatal_accept_error_stops_server() {
+    let listener = FailingListener {
+        listener: tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap(),
+        errors: parking_lot::Mutex::new(vec![std::io::ErrorKind::InvalidInput.into()]),
+    };
+
+    let server_task = ServerBuilder::new()
+        .serve_listener(listener, test_request_handler, TestApplicationContext)
+        .unwrap();
+
+    let result = tokio::time::timeout(Duration::from_secs(1), server_task)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn serve_unix_listener() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let socket_path = std::env::temp_dir().join(format!(
+        "hyper-accelerator-serve-unix-listener-{}.sock",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&socket_path);
+    let listener = tokio::net::UnixListener::bind(&socket_path).unwrap();
+
+    let server_task = ServerBuilder::new()
+        .serve_listener(listener, test_request_handler, TestApplicationContext)
+        .unwrap();
+    assert_eq!(
+        server_task.local_address(),
+        &SocketAddress::Unix(Some(socket_path.clone()))
+    );
+
+    let mut stream = tokio::net::UnixStream::connect(&socket_path).await.unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\r\n\r\ntest_request_handler"));
+
+    server_task.abort();
+    std::fs::remove_file(socket_path).unwrap();
+}
+
+#[cfg(feature = "tls")]
+mod tls {
+    use std::{
+        net::SocketAddr,
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::Duration,
+    };
+
+    use crate::{
+        request_handler::{ErrorResponse, Request, Response},
+        server::{
+            run_https_tcp_server, ConnectionErrorKind, ServerBuilder, ServerHandle, SocketAddress,
+            TlsConfig, TlsInfo,
+        },
+    };
+
+    use super::{TestApplicationContext, TestRequestContext};
+
+    struct TestCertificate {
+        cert_pem: String,
+        key_pem: String,
+    }
+
+    fn self_signed_certificate(server_name: &str) -> TestCertificate {
+        let certified_key = rcgen::generate_simple_self_signed(vec![server_name.into()]).unwrap();
+        TestCertificate {
+            cert_pem: certified_key.cert.pem(),
+            key_pem: certified_key.key_pair.serialize_pem(),
+        }
+    }
+
+    fn write_certificate(
+        dir: &Path,
+        name: &str,
+        certificate: &TestCertificate,
+    ) -> (PathBuf, PathBuf) {
+        let cert_path = dir.join(format!("{name}.crt"));
+        let key_path = dir.join(format!("{name}.key"));
+        std::fs::write(&cert_path, &certificate.cert_pem).unwrap();
+        std::fs::write(&key_path, &certificate.key_pem).unwrap();
+        (cert_path, key_path)
+    }
+
+    fn test_dir(test_name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "hyper-accelerator-{test_name}-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn tcp_address(server_task: &ServerHandle) -> SocketAddr {
+        match server_task.local_address() {
+            SocketAddress::Tcp(address) => *address,
+            _ => panic!("not a TCP server"),
+        }
+    }
+
+    fn client_trusting(
+        certificate: &TestCertificate,
+        server_address: SocketAddr,
+    ) -> reqwest::ClientBuilder {
+        reqwest::Client::builder()
+            .add_root_certificate(
+                reqwest::Certificate::from_pem(certificate.cert_pem.as_bytes()).unwrap(),
+            )
+            .resolve("localhost", server_address)
+            .resolve("sni.localhost", server_address)
+    }
+
+    async fn peer_certificate_count(
+        req: Request,
+        _app_context: Arc<TestApplicationContext>,
+        _request_context: TestRequestContext,
+    ) -> Result<Response, ErrorResponse> {
+        let tls_info = req.extensions().get::<TlsInfo>().unwrap();
+        Ok(Response::new(
+            tls_info.peer_certificates.len().to_string().into(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn https_with_sni() {
+        let dir = test_dir("https_with_sni");
+        let default_certificate = self_signed_certificate("localhost");
+        let sni_certificate = self_signed_certificate("sni.localhost");
+        let (default_cert_path, default_key_path) =
+            write_certificate(&dir, "default", &default_certificate);
+        let (sni_cert_path, sni_key_path) = write_certificate(&dir, "sni", &sni_certificate);
+
+        let server_task = run_https_tcp_server(
+            ("127.0.0.1", 0),
+            super::test_request_handler,
+            TestApplicationContext,
+            TlsConfig::new(default_cert_path, default_key_path).sni_certificate(
+                "sni.localhost",
+                sni_cert_path,
+                sni_key_path,
+            ),
+        )
+        .await
+        .unwrap();
+        let server_address = tcp_address(&server_task);
+
+        for (certificate, url) in [
+            (
+                &default_certificate,
+                format!("https://localhost:{}", server_address.port()),
+            ),
+            (
+                &sni_certificate,
+                format!("https://sni.localhost:{}", server_address.port()),
+            ),
+        ] {
+            let response = client_trusting(certificate, server_address)
+                .build()
+                .unwrap()
+                .get(url)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.text().await.unwrap(), "test_request_handler");
+        }
+
+        assert!(client_trusting(&default_certificate, server_address)
+            .build()
+            .unwrap()
+            .get(format!("https://sni.localhost:{}", server_address.port()))
+            .send()
+            .await
+            .is_err());
+
+        server_task.abort();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn https_with_client_certificate() {
+        let dir = test_dir("https_with_client_certificate");
+        let server_certificate = self_signed_certificate("localhost");
+        let (server_cert_path, server_key_path) =
+            write_certificate(&dir, "server", &server_certificate);
+
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let mut ca_params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+        let ca_cert_path = dir.join("ca.crt");
+        std::fs::write(&ca_cert_path, ca_cert.pem()).unwrap();
+
+        let client_key = rcgen::KeyPair::generate().unwrap();
+        let mut client_params = rcgen::CertificateParams::new(vec!["client".into()]).unwrap();
+        client_params.extended_key_usages = vec![rcgen::ExtendedKeyUsagePurpose::ClientAuth];
+        let client_cert = client_params
+            .signed_by(&client_key, &ca_cert, &ca_key)
+            .unwrap();
+        let client_identity = reqwest::Identity::from_pkcs8_pem(
+            client_cert.pem().as_bytes(),
+            client_key.serialize_pem().as_bytes(),
+        )
+        .unwrap();
+
+        let server_task = run_https_tcp_server(
+            ("127.0.0.1", 0),
+            peer_certificate_count,
+            TestApplicationContext,
+            TlsConfig::new(server_cert_path, server_key_path)
+                .client_certificate_verification(ca_cert_path, true),
+        )
+        .await
+        .unwrap();
+        let server_address = tcp_address(&server_task);
+        let url = format!("https://localhost:{}", server_address.port());
+
+        let response = client_trusting(&server_certificate, server_address)
+            .identity(client_identity)
+            .build()
+            .unwrap()
+            .get(&url)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "1");
+
+        assert!(client_trusting(&server_certificate, server_address)
+            .build()
+            .unwrap()
+            .get(&url)
+            .send()
+            .await
+            .is_err());
+
+        server_task.abort();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tls_handshake_timeout_closes_silent_connection() {
+        use tokio::io::AsyncReadExt;
+
+        let dir = test_dir("tls_handshake_timeout");
+        let (cert_path, key_path) =
+            write_certificate(&dir, "default", &self_signed_certificate("localhost"));
+
+        let (error_sender, mut error_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let server_task = ServerBuilder::new()
+            .tls(TlsConfig::new(cert_path, key_path))
+            .tls_handshake_timeout(Duration::from_millis(100))
+            .connection_error_handler(move |connection_error| {
+                error_sender.send(connection_error.kind).unwrap();
+            })
+            .serve(
+                ("127.0.0.1", 0),
+                super::test_request_handler,
+                TestApplicationContext,
+            )
+            .await
+            .unwrap();
+
+        let mut stream = tokio::net::TcpStream::connect(tcp_address(&server_task))
+            .await
+            .unwrap();
+        let read = tokio::time::timeout(Duration::from_secs(1), stream.read(&mut [0u8; 16]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read, 0);
+        assert_eq!(
+            error_receiver.recv().await.unwrap(),
+            ConnectionErrorKind::Timeout
+        );
+
+        server_task.abort();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn https_certificate_reload() {
+        let dir = test_dir("https_certificate_reload");
+        let old_certificate = self_signed_certificate("localhost");
+        let (cert_path, key_path) = write_certificate(&dir, "server", &old_certificate);
+
+        let server_task = run_https_tcp_server(
+            ("127.0.0.1", 0),
+            super::test_request_handler,
+            TestApplicationContext,
+            TlsConfig::new(cert_path, key_path).reload_interval(Duration::from_millis(50)),
+        )
+        .await
+        .unwrap();
+        let server_address = tcp_address(&server_task);
+        let url = format!("https://localhost:{}", server_address.port());
+
+        let old_client = client_trusting(&old_certificate, server_address)
+            .build()
+            .unwrap();
+        let response = old_client.get(&url).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "test_request_handler");
+
+        let new_certificate = self_signed_certificate("localhost");
+        write_certificate(&dir, "server", &new_certificate);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let response = client_trusting(&new_certificate, server_address)
+            .build()
+            .unwrap()
+            .get(&url)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "test_request_handler");
+
+        // the pooled connection of the old client survives the reload
+        let response = old_client.get(&url).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "test_request_handler");
+
+        server_task.abort();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn https_certificate_reload_is_retried() {
+        let dir = test_dir("https_certificate_reload_is_retried");
+        let old_certificate = self_signed_certificate("localhost");
+        let (cert_path, key_path) = write_certificate(&dir, "server", &old_certificate);
+
+        let server_task = run_https_tcp_server(
+            ("127.0.0.1", 0),
+            super::test_request_handler,
+            TestApplicationContext,
+            TlsConfig::new(&cert_path, &key_path).reload_interval(Duration::from_millis(50)),
+        )
+        .await
+        .unwrap();
+        let server_address = tcp_address(&server_task);
+        let url = format!("https://localhost:{}", server_address.port());
+
+        let response = client_trusting(&old_certificate, server_address)
+            .build()
+            .unwrap()
+            .get(&url)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "test_request_handler");
+
+        // the key is caught half-written, the reload fails
+        let new_certificate = self_signed_certificate("localhost");
+        write_certificate(&dir, "server", &new_certificate);
+        std::fs::write(&key_path, &new_certificate.key_pem[..32]).unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // the rotation completes without changing the modification times
+        let modified = std::fs::metadata(&key_path).unwrap().modified().unwrap();
+        std::fs::write(&key_path, &new_certificate.key_pem).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&key_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let response = client_trusting(&new_certificate, server_address)
+            .build()
+            .unwrap()
+            .get(&url)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "test_request_handler");
+
+        server_task.abort();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+mod test_client {
+    use std::{future::Future, pin::Pin, sync::Arc};
+
+    use crate::{
+        body_ext::BodyExt,
+        error::Error,
+        request_handler::{ErrorResponse, Request, Response},
+        response_body::AsyncStream,
+        test_client::TestClient,
+    };
+
+    use super::{TestApplicationContext, TestRequestContext};
+
+    async fn echo_request(
+        req: Request,
+        _app_context: Arc<TestApplicationContext>,
+        _request_context: TestRequestContext,
+    ) -> Result<Response, ErrorResponse> {
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .map(|value| value.to_str().unwrap().to_string())
+                .unwrap_or_default()
+        };
+        let head = format!(
+            "{} {} x-test={} cookie={}\n",
+            req.method(),
+            req.uri(),
+            header("x-test"),
+            header("cookie"),
+        );
+
+        let (body, _trailers) = req.into_body().collect().await.unwrap().aggregate();
+        let mut resp = head.into_bytes();
+        resp.extend(body.unwrap_or_default());
+
+        Ok(Response::new(resp.into()))
+    }
+
+    pub(super) struct ChunkStream(pub(super) Vec<Vec<u8>>);
+
+    impl AsyncStream<Vec<u8>> for ChunkStream {
+        fn next<'a>(
+            &'a mut self,
+        ) -> Pin<Box<dyn Future<Output = Result<Option<Vec<u8>>, Error>> + Send + Sync + 'a>>
+        {
+            let chunk = (!self.0.is_empty()).then(|| self.0.remove(0));
+            Box::pin(async move { Ok(chunk) })
+        }
+    }
+
+    #[tokio::test]
+    async fn request_without_body() {
+        let client = TestClient::new(echo_request, TestApplicationContext);
+
+        let mut response = client.get("/path?query=1").send().await.unwrap();
+
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "GET /path?query=1 x-test= cookie=\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn request_with_headers_cookies_and_body() {
+        let client = TestClient::new(echo_request, TestApplicationContext);
+
+        let mut response = client
+            .post("/upload")
+            .header("x-test", "value")
+            .cookie("first", "1")
+            .cookie("second", "2")
+            .body("request body")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "POST /upload x-test=value cookie=first=1; second=2\nrequest body"
+        );
+    }
+
+    #[tokio::test]
+    async fn request_with_body_stream() {
+        let client = TestClient::new(echo_request, TestApplicationContext);
+
+        let mut response = client
+            .put("/stream")
+            .body_stream(ChunkStream(vec![b"first ".to_vec(), b"second".to_vec()]))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "PUT /stream x-test= cookie=\nfirst second"
+        );
+    }
+}
+
+mod routing {
+    use std::sync::Arc;
+
+    use hyper::{
+        header::{
+            HeaderValue, ACCEPT, ALLOW, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, HOST, LOCATION,
+        },
+        Method, StatusCode,
+    };
+
+    use crate::{
+        application_context_trait::ApplicationContextTrait,
+        decorators::debug_log_request_line,
+        request_context_trait::RequestContextTrait,
+        request_handler::{
+            ErrorResponse, Request, RequestHandlerFn, RequestHandlerReturnTrait, Response,
+        },
+        response::create_empty_response,
+        routing::{
+            router_fn, Guard, NestedPath, OpenApi, PathNormalization, PathParams, RouteMetadata,
+            Router, RouterBuilder, RoutingError, TrailingSlash, UrlError,
+        },
+        server::run_http1_tcp_server,
+        test_client::TestClient,
+    };
+
+    use super::{TestApplicationContext, TestRequestContext};
+
+    async fn user_post(
+        _req: Request,
+        _app_context: Arc<TestApplicationContext>,
+        _request_context: TestRequestContext,
+        params: PathParams,
+    ) -> Result<Response, ErrorResponse> {
+        let user_id: u64 = params.parse("user_id")?;
+        let slug = params.get("slug").unwrap_or_default();
+
+        Ok(Response::new(format!("user {user_id}, post {slug}").into()))
+    }
+
+    async fn user_by_name(
+        _req: Request,
+        _app_context: Arc<TestApplicationContext>,
+        _request_context: TestRequestContext,
+        params: PathParams,
+    ) -> Result<Response, ErrorResponse> {
+        let name = params.get("name").unwrap_or_default();
+
+        Ok(Response::new(format!("user named {name}").into()))
+    }
+
+    fn router_builder() -> RouterBuilder<TestApplicationContext, TestRequestContext> {
+        RouterBuilder::new()
+            .route(
+                &[Method::GET],
+                "/users/{user_id:u64}/posts/{slug}",
+                user_post,
+            )
+            .unwrap()
+            .route(&[Method::GET], "/names/{name}", user_by_name)
+            .unwrap()
+            .route(&[Method::DELETE], "/names/{name}", user_by_name)
+            .unwrap()
+    }
+
+    fn router() -> Router<TestApplicationContext, TestRequestContext> {
+        router_builder().build(TestApplicationContext)
+    }
+
+    #[tokio::test]
+    async fn typed_path_params() {
+        let client = TestClient::new(router_fn, router());
+
+        let mut response = client.get("/users/42/posts/hello").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "user 42, post hello"
+        );
+
+        let mut response = client.get("/names/alice").send().await.unwrap();
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "user named alice"
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_path_param_is_bad_request() {
+        let client = TestClient::new(router_fn, router());
+
+        let mut response = client.get("/users/alice/posts/hello").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response
+            .body_mut()
+            .read_to_string()
+            .await
+            .unwrap()
+            .contains("user_id"));
+    }
+
+    #[tokio::test]
+    async fn unknown_path_is_not_found() {
+        let client = TestClient::new(router_fn, router());
+
+        for path in ["/users/42", "/users/42/posts/hello/more", "/names/"] {
+            let response = client.get(path).send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn wrong_method_is_method_not_allowed() {
+        let client = TestClient::new(router_fn, router());
+
+        let response = client.put("/names/alice").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(
+            response.headers().get(ALLOW).unwrap(),
+            "GET, DELETE, HEAD, OPTIONS"
+        );
+    }
+
+    #[tokio::test]
+    async fn options_lists_allowed_methods() {
+        let client = TestClient::new(router_fn, router());
+
+        let response = client
+            .request(Method::OPTIONS, "/users/1/posts/hello")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers().get(ALLOW).unwrap(), "GET, HEAD, OPTIONS");
+
+        let response = client
+            .request(Method::OPTIONS, "/unknown")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn head_is_served_by_get_route() {
+        let client = TestClient::new(router_fn, router());
+
+        let mut response = client
+            .request(Method::HEAD, "/names/alice")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(CONTENT_LENGTH).unwrap(), "16");
+        assert!(response.body_mut().read_all().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn head_response_keeps_content_length() {
+        let server_task = run_http1_tcp_server(("127.0.0.1", 0), router_fn, router())
+            .await
+            .unwrap();
+
+        let response = reqwest::Client::new()
+            .head(format!(
+                "http://{}/names/alice",
+                server_task.local_address()
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(CONTENT_LENGTH).unwrap(), "16");
+
+        server_task.abort();
+    }
+
+    async fn nested_path(
+        req: Request,
+        _app_context: Arc<TestApplicationContext>,
+        _request_context: TestRequestContext,
+    ) -> Result<Response, ErrorResponse> {
+        let nested_path = req.extensions().get::<NestedPath>().unwrap();
+
+        Ok(Response::new(
+            format!("{} {}", nested_path.prefix(), nested_path.remainder()).into(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn nested_routers() {
+        let users = RouterBuilder::new()
+            .path(&[Method::GET], "/", nested_path)
+            .unwrap()
+            .path(&[Method::GET], r"/(\d+)", nested_path)
+            .unwrap()
+            .path_with_params(
+                &[Method::GET],
+                r"/(\d+)/posts/(?P<post>\d+)",
+                |_req, _app, _ctx, captures| {
+                    let body = format!("user {} post {}", &captures[1], &captures["post"]);
+                    async move { Ok(Response::new(body.into())) }
+                },
+            )
+            .unwrap();
+        let admin = RouterBuilder::new()
+            .route(&[Method::GET], "/settings/{*rest}", |req, app, ctx, _| {
+                nested_path(req, app, ctx)
+            })
+            .unwrap()
+            .nest("/users", users)
+            .unwrap();
+        let router = RouterBuilder::new()
+            .nest("/admin", admin)
+            .unwrap()
+            .merge(router_builder())
+            .build(TestApplicationContext);
+        let client = TestClient::new(router_fn, router);
+
+        for (path, expected) in [
+            ("/admin/settings/mail/smtp", "/admin /settings/mail/smtp"),
+            ("/admin/users", "/admin/users /"),
+            ("/admin/users/7", "/admin/users /7"),
+            ("/admin/users/7/posts/3", "user 7 post 3"),
+            ("/names/alice", "user named alice"),
+        ] {
+            let mut response = client.get(path).send().await.unwrap();
+            assert_eq!(
+                response.body_mut().read_to_string().await.unwrap(),
+                expected,
+                "{path}"
+            );
+        }
+
+        let response = client.get("/admin/users/").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn overlapping_routes_are_rejected() {
+        let builder = RouterBuilder::new()
+            .route(&[Method::GET], "/admin/users/{id}", user_by_name)
+            .unwrap();
+        let users = RouterBuilder::new()
+            .route(&[Method::GET, Method::POST], "/{name}", user_by_name)
+            .unwrap();
+
+        assert!(matches!(
+            builder.nest("/admin/users", users).unwrap().try_build(TestApplicationContext),
+            Err(RoutingError::OverlappingRoutes { method, .. }) if method == Method::GET
+        ));
+
+        let builder = RouterBuilder::<TestApplicationContext, TestRequestContext>::new()
+            .route(&[Method::GET], "/users/{id:u64}", user_by_name)
+            .unwrap()
+            .route(&[Method::GET], "/users/{name}", user_by_name)
+            .unwrap();
+        assert!(builder.try_build(TestApplicationContext).is_ok());
+    }
+
+    #[tokio::test]
+    async fn build_keeps_the_first_of_overlapping_routes() {
+        let router = RouterBuilder::<TestApplicationContext, TestRequestContext>::new()
+            .path(
+                &[Method::GET],
+                "/hello",
+                |_req, _app_context, _request_context| async { Ok(Response::new("first".into())) },
+            )
+            .unwrap()
+            .path(
+                &[Method::GET],
+                "/hello",
+                |_req, _app_context, _request_context| async { Ok(Response::new("second".into())) },
+            )
+            .unwrap()
+            .build(TestApplicationContext);
+        let client = TestClient::new(router_fn, router);
+
+        let mut response = client.get("/hello").send().await.unwrap();
+        assert_eq!(response.body_mut().read_to_string().await.unwrap(), "first");
+    }
+
+    #[test]
+    fn nesting_prefix_has_to_be_literal() {
+        for prefix in ["/admin/", "admin", "/users/{id}"] {
+            assert!(
+                RouterBuilder::<TestApplicationContext, TestRequestContext>::new()
+                    .nest(prefix, RouterBuilder::new())
+                    .is_err(),
+                "{prefix}"
+            );
+        }
+    }
+
+    async fn require_authorization<
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+        NextReturnType: RequestHandlerReturnTrait,
+    >(
+        next: impl RequestHandlerFn<ApplicationContextType, RequestContextType, NextReturnType>,
+        req: Request,
+        app_context: Arc<ApplicationContextType>,
+        request_context: RequestContextType,
+    ) -> Result<Response, ErrorResponse> {
+        if !req.headers().contains_key(AUTHORIZATION) {
+            return Err(create_empty_response(StatusCode::UNAUTHORIZED).into());
+        }
+
+        next(req, app_context, request_context).await
+    }
+
+    async fn tag_response<
+        ApplicationContextType: ApplicationContextTrait,
+        RequestContextType: RequestContextTrait<ApplicationContextType>,
+        NextReturnType: RequestHandlerReturnTrait,
+    >(
+        next: impl RequestHandlerFn<ApplicationContextType, RequestContextType, NextReturnType>,
+        req: Request,
+        app_context: Arc<ApplicationContextType>,
+        request_context: RequestContextType,
+    ) -> Result<Response, ErrorResponse> {
+        let mut resp = next(req, app_context, request_context).await?;
+        resp.headers_mut()
+            .append("x-tag", HeaderValue::from_static("tagged"));
+        Ok(resp)
+    }
+
+    #[tokio::test]
+    async fn route_and_group_middlewares() {
+        let private = RouterBuilder::new()
+            .route(&[Method::GET], "/private/{name}", user_by_name)
+            .unwrap()
+            .route(&[Method::DELETE], "/private/{name}", user_by_name)
+            .unwrap()
+            .route_middleware(tag_response)
+            .middleware(require_authorization)
+            .middleware(debug_log_request_line);
+        let router = router_builder()
+            .merge(private)
+            .build(TestApplicationContext);
+        let client = TestClient::new(router_fn, router);
+
+        let response = client.get("/names/alice").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        for method in [Method::GET, Method::DELETE] {
+            let response = client
+                .request(method.clone(), "/private/alice")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{method}");
+        }
+
+        let mut response = client
+            .get("/private/alice")
+            .header(AUTHORIZATION, "secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("x-tag"), None);
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "user named alice"
+        );
+
+        let response = client
+            .delete("/private/alice")
+            .header(AUTHORIZATION, "secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.headers().get("x-tag").unwrap(), "tagged");
+    }
+
+    async fn fallback(
+        req: Request,
+        _app_context: Arc<TestApplicationContext>,
+        _request_context: TestRequestContext,
+    ) -> Result<Response, ErrorResponse> {
+        let prefix = req
+            .extensions()
+            .get::<NestedPath>()
+            .map_or("-", |nested_path| nested_path.prefix());
+
+        Ok(Response::new(
+            format!("fallback {} {prefix}", req.uri().path()).into(),
+        ))
+    }
+
+    fn status_envelope(e: ErrorResponse) -> ErrorResponse {
+        let (mut parts, _body) = e.0.into_parts();
+        parts.headers.insert(
+            hyper::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        let body = format!(r#"{{"status":{}}}"#, parts.status.as_u16());
+
+        Response::from_parts(parts, body.into()).into()
+    }
+
+    #[tokio::test]
+    async fn fallback_and_error_handler() {
+        let api = RouterBuilder::new()
+            .route(&[Method::GET], "/private/{name}", user_by_name)
+            .unwrap()
+            .middleware(require_authorization)
+            .fallback(fallback)
+            .error_handler(|mut e| {
+                e.0.headers_mut()
+                    .insert("x-api-error", HeaderValue::from_static("1"));
+                e
+            });
+        let router = router_builder()
+            .nest("/api", api)
+            .unwrap()
+            .fallback(fallback)
+            .error_handler(status_envelope)
+            .build(TestApplicationContext);
+        let client = TestClient::new(router_fn, router);
+
+        for (path, expected) in [
+            ("/unknown", "fallback /unknown -"),
+            ("/apis", "fallback /apis -"),
+            ("/api", "fallback /api /api"),
+            ("/api/unknown", "fallback /api/unknown /api"),
+        ] {
+            let mut response = client.get(path).send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.body_mut().read_to_string().await.unwrap(),
+                expected,
+                "{path}"
+            );
+        }
+
+        for (method, path, status, api_error) in [
+            (
+                Method::GET,
+                "/api/private/alice",
+                StatusCode::UNAUTHORIZED,
+                true,
+            ),
+            (
+                Method::PUT,
+                "/names/alice",
+                StatusCode::METHOD_NOT_ALLOWED,
+                false,
+            ),
+            (
+                Method::GET,
+                "/users/abc/posts/x",
+                StatusCode::BAD_REQUEST,
+                false,
+            ),
+        ] {
+            let mut response = client.request(method, path).send().await.unwrap();
+            assert_eq!(response.status(), status);
+            assert_eq!(
+                response.headers().contains_key("x-api-error"),
+                api_error,
+                "{path}"
+            );
+            assert_eq!(
+                response.body_mut().read_to_string().await.unwrap(),
+                format!(r#"{{"status":{}}}"#, status.as_u16())
+            );
+        }
+    }
+
+    #[test]
+    fn named_route_urls() {
+        let admin = RouterBuilder::new()
+            .route(
+                &[Method::GET],
+                "/users/{user_id:u64}/posts/{slug}",
+                user_post,
+            )
+            .unwrap()
+            .name("admin_post");
+        let admin_urls = admin.urls();
+        let builder = router_builder()
+            .name("user_by_name")
+            .nest("/admin", admin)
+            .unwrap();
+        let route_urls = builder.urls();
+        assert_eq!(
+            route_urls.url_for("user_by_name").build(),
+            Err(UrlError::RouterNotBuilt)
+        );
+
+        let router = builder.build(TestApplicationContext);
+
+        assert_eq!(
+            router
+                .url_for("user_by_name")
+                .param("name", "Jane Doe")
+                .query("tab", "posts")
+                .build()
+                .unwrap(),
+            "/names/Jane%20Doe?tab=posts"
+        );
+        for route_urls in [route_urls, admin_urls] {
+            assert_eq!(
+                route_urls
+                    .url_for("admin_post")
+                    .param("user_id", 7)
+                    .param("slug", "hello")
+                    .build()
+                    .unwrap(),
+                "/admin/users/7/posts/hello"
+            );
+        }
+        assert!(matches!(
+            router.url_for("admin_post").param("user_id", 7).build(),
+            Err(UrlError::MissingParam { .. })
+        ));
+    }
+
+    #[test]
+    fn duplicate_route_names_are_rejected() {
+        let builder = router_builder()
+            .name("user")
+            .path(&[Method::GET], "/user", fallback)
+            .unwrap()
+            .name("user");
+
+        assert!(matches!(
+            builder.try_build(TestApplicationContext),
+            Err(RoutingError::DuplicateRouteName(name)) if name == "user"
+        ));
+    }
+
+    async fn tenant_item(
+        _req: Request,
+        _app_context: Arc<TestApplicationContext>,
+        _request_context: TestRequestContext,
+        params: PathParams,
+    ) -> Result<Response, ErrorResponse> {
+        let tenant = params.get("tenant").unwrap_or("-");
+        let name = params.get("name").unwrap_or_default();
+
+        Ok(Response::new(
+            format!("tenant {tenant}, item {name}").into(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn guards_select_the_route() {
+        let router = router_builder()
+            .route(&[Method::GET], "/names/{name}", tenant_item)
+            .unwrap()
+            .guard(Guard::accept("application/vnd.x.v2+json"))
+            .route(&[Method::GET], "/items/{name}", tenant_item)
+            .unwrap()
+            .guard(Guard::host("{tenant}.example.com").unwrap())
+            .route(&[Method::POST], "/items/{name}", user_by_name)
+            .unwrap()
+            .guard(Guard::content_type("application/json"))
+            .guard(Guard::header_present(AUTHORIZATION))
+            .route(&[Method::GET], "/search/{name}", user_by_name)
+            .unwrap()
+            .guard(Guard::query_value("version", "2"))
+            .build(TestApplicationContext);
+        let client = TestClient::new(router_fn, router);
+
+        let mut response = client
+            .get("/names/alice")
+            .header(ACCEPT, "text/html, application/vnd.x.v2+json;q=0.9")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "tenant -, item alice"
+        );
+        for accept in ["*/*", "application/vnd.x.v2+json;q=0"] {
+            let mut response = client
+                .get("/names/alice")
+                .header(ACCEPT, accept)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(
+                response.body_mut().read_to_string().await.unwrap(),
+                "user named alice"
+            );
+        }
+
+        let mut response = client
+            .get("/items/ball")
+            .header(HOST, "Acme.example.com:8080")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "tenant acme, item ball"
+        );
+        let response = client
+            .get("/items/ball")
+            .header(HOST, "example.com")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = client
+            .post("/items/ball")
+            .header(HOST, "acme.example.com")
+            .header(CONTENT_TYPE, "application/json; charset=utf-8")
+            .header(AUTHORIZATION, "token")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = client
+            .post("/items/ball")
+            .header(HOST, "acme.example.com")
+            .header(CONTENT_TYPE, "text/plain")
+            .header(AUTHORIZATION, "token")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers().get(ALLOW).unwrap(), "GET, HEAD, OPTIONS");
+
+        let response = client.get("/search/x?version=2").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = client.get("/search/x?version=1").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn routes_with_different_guards_do_not_overlap() {
+        let builder = router_builder()
+            .route(&[Method::GET], "/names/{name}", user_by_name)
+            .unwrap()
+            .guard(Guard::accept("application/vnd.x.v2+json"));
+        assert!(builder.try_build(TestApplicationContext).is_ok());
+
+        let builder = router_builder()
+            .route(&[Method::GET], "/names/{name}", user_by_name)
+            .unwrap()
+            .guard(Guard::query("a"))
+            .guard(Guard::query("b"))
+            .route(&[Method::GET], "/names/{name}", user_by_name)
+            .unwrap()
+            .guard(Guard::query("b"))
+            .guard(Guard::query("a"));
+        assert!(matches!(
+            builder.try_build(TestApplicationContext),
+            Err(RoutingError::OverlappingRoutes { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn route_introspection_and_openapi() {
+        let docs = RouterBuilder::new()
+            .openapi("/openapi.json", OpenApi::new("Test API", "1.0.0"))
+            .unwrap();
+        let router = router_builder()
+            .metadata(
+                RouteMetadata::new()
+                    .summary("Deletes a user")
+                    .tag("users")
+                    .response(StatusCode::NO_CONTENT, "deleted"),
+            )
+            .name("delete_user")
+            .path(&[Method::GET], r"/legacy/\d+", fallback)
+            .unwrap()
+            .nest("/docs", docs)
+            .unwrap()
+            .build(TestApplicationContext);
+
+        let routes: Vec<_> = router
+            .routes()
+            .map(|route| (route.methods.to_vec(), route.pattern, route.name))
+            .collect();
+        assert_eq!(
+            routes,
+            [
+                (vec![Method::GET], "/users/{user_id:u64}/posts/{slug}", None),
+                (vec![Method::GET], "/names/{name}", None),
+                (vec![Method::DELETE], "/names/{name}", Some("delete_user")),
+                (vec![Method::GET], r"/legacy/\d+", None),
+                (vec![Method::GET], "/docs/openapi.json", None),
+            ]
+        );
+
+        let client = TestClient::new(router_fn, router);
+        let mut response = client.get("/docs/openapi.json").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let document: serde_json::Value =
+            serde_json::from_str(&response.body_mut().read_to_string().await.unwrap()).unwrap();
+
+        assert_eq!(document["openapi"], "3.1.0");
+        assert_eq!(document["info"]["title"], "Test API");
+        let paths = document["paths"].as_object().unwrap();
+        assert_eq!(
+            paths.keys().collect::<Vec<_>>(),
+            ["/names/{name}", "/users/{user_id}/posts/{slug}"]
+        );
+        assert_eq!(
+            document["paths"]["/users/{user_id}/posts/{slug}"]["get"]["parameters"][0],
+            serde_json::json!({
+                "name": "user_id",
+                "in": "path",
+                "required": true,
+                "schema": { "type": "integer", "minimum": 0 },
+            })
+        );
+        let delete = &document["paths"]["/names/{name}"]["delete"];
+        assert_eq!(delete["operationId"], "delete_user");
+        assert_eq!(delete["summary"], "Deletes a user");
+        assert_eq!(delete["tags"][0], "users");
+        assert_eq!(delete["responses"]["204"]["description"], "deleted");
+    }
+
+    #[tokio::test]
+    async fn path_normalization() {
+        let client = TestClient::new(router_fn, router());
+        for path in [
+            "/names/alice/",
+            "/Names/alice",
+            "/names//alice",
+            "/n%61mes/alice",
+        ] {
+            let response = client.get(path).send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{path}");
+        }
+
+        let router = router_builder()
+            .normalization(
+                PathNormalization::new()
+                    .trailing_slash(TrailingSlash::Match)
+                    .collapse_slashes(true)
+                    .decode_percent(true)
+                    .case_insensitive(true),
+            )
+            .build(TestApplicationContext);
+        let client = TestClient::new(router_fn, router);
+        for (path, body) in [
+            ("/names/alice/", "user named alice"),
+            ("/NAMES/Alice", "user named Alice"),
+            ("//names//alice", "user named alice"),
+            ("/n%61mes/J%C3%A1nos%20K", "user named János K"),
+        ] {
+            let mut response = client.get(path).send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{path}");
+            assert_eq!(
+                response.body_mut().read_to_string().await.unwrap(),
+                body,
+                "{path}"
+            );
+        }
+        for path in ["/names/a%2Fb", "/names/../names/alice", "/names/%2E%2E"] {
+            let response = client.get(path).send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{path}");
+        }
+
+        let router = router_builder()
+            .normalization(PathNormalization::new().trailing_slash(TrailingSlash::Redirect))
+            .build(TestApplicationContext);
+        let client = TestClient::new(router_fn, router);
+        let response = client.delete("/names/alice/?force=1").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            response.headers().get(LOCATION).unwrap(),
+            "/names/alice?force=1"
+        );
+        let response = client.get("/names/").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let router = router_builder()
+            .route(&[Method::GET], "/{name}", user_by_name)
+            .unwrap()
+            .normalization(
+                PathNormalization::new()
+                    .trailing_slash(TrailingSlash::Redirect)
+                    .collapse_slashes(true)
+                    .decode_percent(true),
+            )
+            .build(TestApplicationContext);
+        let client = TestClient::new(router_fn, router);
+        for (path, location) in [
+            ("//evil.com/", "/evil.com"),
+            ("//names//J%C3%A1nos%20K/", "/names/J%C3%A1nos%20K"),
+        ] {
+            let response = client.get(path).send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT, "{path}");
+            assert_eq!(response.headers()[LOCATION], location, "{path}");
+        }
+
+        let router = router_builder()
+            .path(
+                &[Method::GET],
+                "//[a-z.]+",
+                |_req, _app_context, _request_context| async { Ok(Response::new("".into())) },
+            )
+            .unwrap()
+            .normalization(PathNormalization::new().trailing_slash(TrailingSlash::Redirect))
+            .build(TestApplicationContext);
+        let client = TestClient::new(router_fn, router);
+        let response = client.get("//evil.com").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = client.get("//evil.com/").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
+
+mod extract {
+    use std::sync::Arc;
+
+    use hyper::{header::CONTENT_TYPE, StatusCode};
+
+    use crate::{
+        extract::{parse_query, FormBody, JsonBody, Multipart, MultipartLimits},
+        request_handler::{ErrorResponse, Request, Response},
+        test_client::TestClient,
+    };
+
+    use super::{test_client::ChunkStream, TestApplicationContext, TestRequestContext};
+
+    #[derive(serde::Deserialize)]
+    struct NewUser {
+        name: String,
+        age: u8,
+    }
+
+    async fn create_user(
+        req: Request,
+        _app_context: Arc<TestApplicationContext>,
+        _request_context: TestRequestContext,
+    ) -> Result<Response, ErrorResponse> {
+        let user: NewUser = JsonBody::new().limit(64).extract(req).await?;
+
+        Ok(Response::new(format!("{} {}", user.name, user.age).into()))
+    }
+
+    #[tokio::test]
+    async fn json_body() {
+        let client = TestClient::new(create_user, TestApplicationContext);
+
+        let mut response = client
+            .post("/users")
+            .header(CONTENT_TYPE, "application/json; charset=utf-8")
+            .body(r#"{"name": "alice", "age": 30}"#)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "alice 30"
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_json_body_is_bad_request() {
+        let client = TestClient::new(create_user, TestApplicationContext);
+
+        let mut response = client
+            .post("/users")
+            .header(CONTENT_TYPE, "application/json")
+            .body("{\n  \"name\": \"alice\",\n  \"age\": 300\n}")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = response.body_mut().read_to_string().await.unwrap();
+        assert!(
+            body.starts_with("invalid JSON at line 3, column 12, path 'age'"),
+            "{body}"
+        );
+    }
+
+    #[tokio::test]
+    async fn json_content_type_is_required() {
+        let client = TestClient::new(create_user, TestApplicationContext);
+
+        for content_type in [Some("text/plain"), None] {
+            let mut request = client
+                .post("/users")
+                .body(r#"{"name": "alice", "age": 30}"#);
+            if let Some(content_type) = content_type {
+                request = request.header(CONTENT_TYPE, content_type);
+            }
+            let response = request.send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        }
+    }
+
+    #[tokio::test]
+    async fn large_json_body_is_rejected() {
+        let client = TestClient::new(create_user, TestApplicationContext);
+        let name = "a".repeat(64);
+
+        let response = client
+            .post("/users")
+            .header(CONTENT_TYPE, "application/json")
+            .body(format!(r#"{{"name": "{name}", "age": 30}}"#))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let response = client
+            .post("/users")
+            .header(CONTENT_TYPE, "application/json")
+            .body_stream(ChunkStream(vec![
+                br#"{"name": ""#.to_vec(),
+                name.into_bytes(),
+                br#"", "age": 30}"#.to_vec(),
+            ]))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    const BOUNDARY: &str = "----boundary";
+
+    fn multipart_body() -> Vec<u8> {
+        format!(
+            "preamble\r\n--{BOUNDARY}\r\n\
+             Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+             Hello\r\nWorld\r\n--{BOUNDARY}\r\n\
+             Content-Disposition: form-data; name=\"file\"; filename=\"notes.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\n\
+             first line\r\n--not the boundary\r\n--{BOUNDARY}--\r\n"
+        )
+        .into_bytes()
+    }
+
+    async fn upload(
+        req: Request,
+        _app_context: Arc<TestApplicationContext>,
+        _request_context: TestRequestContext,
+    ) -> Result<Response, ErrorResponse> {
+        let mut multipart = Multipart::from_request(req)?.limits(MultipartLimits {
+            part_size: 64,
+            ..Default::default()
+        });
+
+        let mut summary = Vec::new();
+        while let Some(part) = multipart.next_part().await? {
+            let name = part.name().unwrap_or_default().to_string();
+            match part.file_name().map(str::to_string) {
+                Some(file_name) => {
+                    let content_type = part.content_type().unwrap_or_default().to_string();
+                    let path = std::env::temp_dir()
+                        .join(format!("hyper-accelerator-upload-{}", std::process::id()));
+                    let size = part.save_to(&path).await?;
+                    let data = tokio::fs::read_to_string(&path).await.unwrap();
+                    tokio::fs::remove_file(&path).await.unwrap();
+                    summary.push(format!("{name}={file_name} {content_type} {size} {data:?}"));
+                }
+                None => summary.push(format!("{name}={:?}", part.text().await?)),
+            }
+        }
+
+        Ok(Response::new(summary.join("\n").into()))
+    }
+
+    #[tokio::test]
+    async fn multipart_parts_spanning_frames() {
+        let client = TestClient::new(upload, TestApplicationContext);
+        let body = multipart_body();
+
+        // small chunks split the delimiters and the headers across frames
+        for chunk_size in [1, 3, 7, 16, body.len()] {
+            let mut response = client
+                .post("/upload")
+                .header(
+                    CONTENT_TYPE,
+                    format!("multipart/form-data; boundary={BOUNDARY}"),
+                )
+                .body_stream(ChunkStream(
+                    body.chunks(chunk_size).map(<[u8]>::to_vec).collect(),
+                ))
+                .send()
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK, "{chunk_size}");
+            assert_eq!(
+                response.body_mut().read_to_string().await.unwrap(),
+                "title=\"Hello\\r\\nWorld\"\n\
+                 file=notes.txt text/plain 30 \"first line\\r\\n--not the boundary\"",
+                "{chunk_size}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn multipart_errors() {
+        let client = TestClient::new(upload, TestApplicationContext);
+
+        let response = client
+            .post("/upload")
+            .header(CONTENT_TYPE, "application/json")
+            .body("{}")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let content_type = format!("multipart/form-data; boundary={BOUNDARY}");
+        let mut body = multipart_body();
+        body.truncate(body.len() - 30);
+        let response = client
+            .post("/upload")
+            .header(CONTENT_TYPE, content_type.as_str())
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"big\"\r\n\r\n{}\r\n--{BOUNDARY}--",
+            "x".repeat(100)
+        );
+        let mut response = client
+            .post("/upload")
+            .header(CONTENT_TYPE, content_type.as_str())
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "part 'big' is larger than 64 bytes"
+        );
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Search {
+        q: String,
+        page: Option<u32>,
+        #[serde(default)]
+        tag: Vec<String>,
+    }
+
+    async fn search(
+        req: Request,
+        _app_context: Arc<TestApplicationContext>,
+        _request_context: TestRequestContext,
+    ) -> Result<Response, ErrorResponse> {
+        let search: Search = parse_query(&req)?;
+
+        Ok(Response::new(
+            format!("{} {:?} {:?}", search.q, search.page, search.tag).into(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn query_string() {
+        let client = TestClient::new(search, TestApplicationContext);
+
+        let mut response = client
+            .get("/search?q=rust+http%21&tag=a&tag=b%20c")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            r#"rust http! None ["a", "b c"]"#
+        );
+
+        let mut response = client.get("/search?q=x&page=-1").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = response.body_mut().read_to_string().await.unwrap();
+        assert!(body.starts_with("invalid value for field 'page'"), "{body}");
+    }
+
+    async fn login(
+        req: Request,
+        _app_context: Arc<TestApplicationContext>,
+        _request_context: TestRequestContext,
+    ) -> Result<Response, ErrorResponse> {
+        let user: NewUser = FormBody::new().extract(req).await?;
+
+        Ok(Response::new(format!("{} {}", user.name, user.age).into()))
+    }
+
+    #[tokio::test]
+    async fn form_body() {
+        let client = TestClient::new(login, TestApplicationContext);
+
+        let mut response = client
+            .post("/login")
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body("name=J%C3%BCrgen+M&age=41")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "Jürgen M 41"
+        );
+
+        let mut response = client
+            .post("/login")
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body("name=alice")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "invalid form data: missing field `age`"
+        );
+
+        let response = client
+            .post("/login")
+            .header(CONTENT_TYPE, "application/json")
+            .body("name=alice&age=30")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
+
+mod negotiation {
+    use std::sync::Arc;
+
+    use hyper::{
+        header::{ACCEPT, CONTENT_TYPE, VARY},
+        StatusCode,
+    };
+
+    use crate::{
+        request_handler::{ErrorResponse, Request, Response},
+        response::create_negotiated_response,
+        test_client::TestClient,
+    };
+
+    use super::{TestApplicationContext, TestRequestContext};
+
+    #[derive(serde::Serialize)]
+    struct Resource {
+        id: u64,
+    }
+
+    async fn resource(
+        req: Request,
+        _app_context: Arc<TestApplicationContext>,
+        _request_context: TestRequestContext,
+    ) -> Result<Response, ErrorResponse> {
+        Ok(create_negotiated_response(
+            req.headers(),
+            StatusCode::OK,
+            &Resource { id: 7 },
+        )?)
+    }
+
+    #[tokio::test]
+    async fn negotiated_response() {
+        let client = TestClient::new(resource, TestApplicationContext);
+
+        let mut response = client
+            .get("/resource")
+            .header(ACCEPT, "text/html, application/json;q=0.8, */*;q=0.1")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(response.headers()[VARY], "accept");
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            r#"{"id":7}"#
+        );
+
+        let response = client
+            .get("/resource")
+            .header(ACCEPT, "text/html, */*;q=0")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+        assert_eq!(response.headers()[VARY], "accept");
+    }
+
+    #[cfg(feature = "yaml")]
+    #[tokio::test]
+    async fn negotiated_yaml_response() {
+        let client = TestClient::new(resource, TestApplicationContext);
+
+        let mut response = client
+            .get("/resource")
+            .header(ACCEPT, "application/json;q=0.5, text/yaml")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/yaml");
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "id: 7\n"
+        );
+    }
+}
+
+mod into_response {
+    use std::sync::Arc;
+
+    use hyper::{header::CONTENT_TYPE, http::HeaderValue, Method, StatusCode};
+
+    use crate::{
+        into_response::{handler, route_handler, Json},
+        request_handler::{ErrorResponse, Request},
+        routing::{router_fn, PathParams, RouterBuilder},
+        test_client::TestClient,
+    };
+
+    use super::{TestApplicationContext, TestRequestContext};
+
+    async fn hello(
+        _req: Request,
+        _app_context: Arc<TestApplicationContext>,
+        _request_context: TestRequestContext,
+    ) -> &'static str {
+        "Hello World!"
+    }
+
+    #[derive(serde::Serialize)]
+    struct User {
+        id: u64,
+    }
+
+    async fn user(
+        _req: Request,
+        _app_context: Arc<TestApplicationContext>,
+        _request_context: TestRequestContext,
+        params: PathParams,
+    ) -> Result<(StatusCode, Json<User>), (StatusCode, String)> {
+        match params.parse("user_id") {
+            Ok(id) if id > 0 => Ok((StatusCode::CREATED, Json(User { id }))),
+            _ => Err((StatusCode::NOT_FOUND, "no such user".to_string())),
+        }
+    }
+
+    #[tokio::test]
+    async fn handlers_returning_plain_values() {
+        let client = TestClient::new(handler(hello), TestApplicationContext);
+        let mut response = client.get("/").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/plain");
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "Hello World!"
+        );
+
+        let router = RouterBuilder::new()
+            .path(&[Method::GET], "/hello", handler(hello))
+            .unwrap()
+            .route(&[Method::POST], "/users/{user_id:u64}", route_handler(user))
+            .unwrap()
+            .build(TestApplicationContext);
+        let client = TestClient::new(router_fn, router);
+
+        let mut response = client.post("/users/7").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            r#"{"id":7}"#
+        );
+
+        let mut response = client.post("/users/0").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.body_mut().read_to_string().await.unwrap(),
+            "no such user"
+        );
+
+        let response = client.get("/hello").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    #[tokio::test]
+    async fn handler_errors_reach_the_error_handler() {
+        let router = RouterBuilder::new()
+            .route(&[Method::POST], "/users/{user_id:u64}", route_handler(user))
+            .unwrap()
+            .error_handler(|ErrorResponse(mut resp)| {
+                resp.headers_mut()
+                    .insert("x-error", HeaderValue::from_static("1"));
+                ErrorResponse(resp)
+            })
+            .build(TestApplicationContext);
+        let client = TestClient::new(router_fn, router);
+
+        let response = client.post("/users/0").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()["x-error"], "1");
+
+        let response = client.post("/users/7").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(response.headers().get("x-error").is_none());
+    }
+}